@@ -0,0 +1,255 @@
+use std::fs;
+use std::io;
+
+use chrono::TimeDelta;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use tabular::{Table, row};
+use viaggiatreno::{Error, Result, StationCandidate, TrainStatus, circulation, time};
+
+use crate::context::{self, Context};
+use crate::output::{self, BranchTrainReport};
+use crate::station;
+use crate::track_train;
+
+/// Branches bundled with the program, which can be extended or overridden by a `branches.json`
+/// file with the same format in the data directory.
+const BUNDLED_BRANCHES: &str = include_str!("../data/branches.json");
+
+/// Categories of the trains shown when no filter is given.
+const REGIONAL_CATEGORIES: &[&str] = &["REG", "RV"];
+
+/// A regional branch line, defined by its ordered list of stations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Branch {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub stations: Vec<BranchStation>,
+}
+
+/// A station of a branch. Small stations often share the beginning of their name with other
+/// stations, so the code can be given to avoid looking the name up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchStation {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+/// A branch as a row of the tabular output formats, with the names of its stations joined by `;`.
+#[derive(Default, Serialize)]
+struct BranchRow<'a> {
+    name: &'a str,
+    description: Option<&'a str>,
+    stations: String,
+}
+
+impl<'a> From<&'a Branch> for BranchRow<'a> {
+    fn from(branch: &'a Branch) -> Self {
+        Self {
+            name: &branch.name,
+            description: branch.description.as_deref(),
+            stations: branch
+                .stations
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>()
+                .join(";"),
+        }
+    }
+}
+
+pub async fn branch(
+    ctx: &Context,
+    name: Option<&str>,
+    stations: &[String],
+    filter: Option<&str>,
+) -> Result<()> {
+    let (title, stations) = match name {
+        Some(name) => {
+            let branch = load_branches()?
+                .into_iter()
+                .find(|b| b.name.eq_ignore_ascii_case(name.trim()))
+                .ok_or_else(|| {
+                    Error::NotFound(format!(
+                        "No branch found with the name \"{}\". Use --list to see the available ones.",
+                        name.trim()
+                    ))
+                })?;
+            let title = branch.description.clone().unwrap_or(branch.name.clone());
+            (title, resolve_stations(ctx, &branch.stations).await?)
+        }
+        None => {
+            let stations = stations
+                .iter()
+                .map(|s| BranchStation {
+                    name: s.trim().to_string(),
+                    code: None,
+                })
+                .collect::<Vec<_>>();
+            let stations = resolve_stations(ctx, &stations).await?;
+            let title = format!(
+                "{} - {}",
+                stations.first().map_or("--", |s| s.name.as_str()),
+                stations.last().map_or("--", |s| s.name.as_str())
+            );
+            (title, stations)
+        }
+    };
+
+    if stations.len() < 2 {
+        return Err(Error::InvalidInput(
+            "A branch needs at least two stations.".to_string(),
+        ));
+    }
+
+    let trains = trains_on_branch(ctx, &stations, filter).await?;
+
+    if !ctx.output.is_human() {
+        let reports: Vec<_> = trains.iter().map(BranchTrainReport::from).collect();
+        return output::print_records(ctx.output, "branch_trains", "branch_train", &reports);
+    }
+
+    println!("Trains currently running on {}:\n", title.cyan());
+
+    if trains.is_empty() {
+        println!("No train found.");
+        return Ok(());
+    }
+
+    let mut table = Table::new("{:<}  {:<}  {:<} {:<}  {:>}  {:<}  {:<}  {:<}");
+    table.add_row(row!(
+        "Train".bold(),
+        "Destination".bold(),
+        "Last detection".bold(),
+        "",
+        "Delay".bold(),
+        "Next stop".bold(),
+        "Scheduled".bold(),
+        "Estimated".bold()
+    ));
+    for train in &trains {
+        let next_stop = train.next_stop();
+        let scheduled_arrival = next_stop.and_then(|s| s.scheduled_arrival);
+        table.add_row(row!(
+            train.label.as_deref().unwrap_or("--").trim().bold(),
+            train.destination.as_deref().unwrap_or("--"),
+            train.last_detection_station.as_deref().unwrap_or("--"),
+            track_train::format_time(train.last_detection_time),
+            train.delay.map_or("--".to_string(), station::format_delay),
+            next_stop.map_or("--", |s| s.station.as_str()),
+            track_train::format_time(scheduled_arrival),
+            track_train::format_estimated_time(scheduled_arrival, train.delay.unwrap_or(0)).bold()
+        ));
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+/// Prints the names of the available branches.
+pub fn list_branches(ctx: &Context) -> Result<()> {
+    let branches = load_branches()?;
+
+    if ctx.output.is_tabular() {
+        let rows: Vec<_> = branches.iter().map(BranchRow::from).collect();
+        return output::print_rows(ctx.output, &rows);
+    } else if !ctx.output.is_human() {
+        return output::print_records(ctx.output, "branches", "branch", &branches);
+    }
+
+    let mut table = Table::new("{:<}  {:<}");
+    for branch in &branches {
+        table.add_row(row!(
+            branch.name.bold(),
+            branch.description.as_deref().unwrap_or("")
+        ));
+    }
+    print!("{table}");
+
+    Ok(())
+}
+
+/// Loads the bundled branches, together with the ones defined in the data directory.
+fn load_branches() -> Result<Vec<Branch>> {
+    let mut branches: Vec<Branch> =
+        serde_json::from_str(BUNDLED_BRANCHES).expect("the bundled branches file should be valid");
+
+    let Some(path) = context::data_dir().map(|d| d.join("branches.json")) else {
+        return Ok(branches);
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(branches),
+        Err(source) => {
+            return Err(Error::Io {
+                message: format!("Cannot read {}", path.display()),
+                source,
+            });
+        }
+    };
+    let user_branches: Vec<Branch> = serde_json::from_str(&content)
+        .map_err(|e| Error::InvalidInput(format!("Invalid {}: {}", path.display(), e)))?;
+
+    // User branches replace the bundled ones with the same name
+    for branch in user_branches {
+        match branches.iter_mut().find(|b| b.name == branch.name) {
+            Some(existing) => *existing = branch,
+            None => branches.push(branch),
+        }
+    }
+
+    Ok(branches)
+}
+
+async fn resolve_stations(
+    ctx: &Context,
+    stations: &[BranchStation],
+) -> Result<Vec<StationCandidate>> {
+    let mut resolved = Vec::with_capacity(stations.len());
+    for station in stations {
+        match &station.code {
+            Some(code) => resolved.push(StationCandidate {
+                name: station.name.clone(),
+                code: code.clone(),
+            }),
+            None => resolved.push(station::find_station_exact(ctx, &station.name).await?),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Finds the trains currently running on a branch: trains that have reached one of its stations
+/// and are going to stop at another one.
+async fn trains_on_branch(
+    ctx: &Context,
+    stations: &[StationCandidate],
+    filter: Option<&str>,
+) -> Result<Vec<TrainStatus>> {
+    let now = time::now();
+    let codes: Vec<_> = stations.iter().map(|s| s.code.as_str()).collect();
+
+    // Trains currently running through the branch are listed on the boards of its stations
+    // around the current time, looking back a bit for trains between two distant stations
+    let mut trains = circulation::circulating_trains(
+        &ctx.client,
+        &codes,
+        now - TimeDelta::minutes(30),
+        |entry| match filter {
+            Some(category) => entry.has_category(category),
+            None => REGIONAL_CATEGORIES.iter().any(|c| entry.has_category(c)),
+        },
+    )
+    .await?;
+
+    trains.retain(|train| {
+        let on_branch = |index: usize| codes.contains(&train.stops[index].station_code.as_str());
+        let Some(reached) = train.last_reached_stop_index() else {
+            return false;
+        };
+        (0..=reached).any(on_branch) && (reached + 1..train.stops.len()).any(on_branch)
+    });
+
+    Ok(trains)
+}
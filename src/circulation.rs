@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use futures::future::join_all;
+
+use crate::client::ViaggiatrenoClient;
+use crate::error::Result;
+use crate::models::{StationBoardEntry, TrainStatus};
+
+/// Finds the trains currently running through the given stations, looking them up on the arrival
+/// and departure boards of each station around the given time.
+/// Only the board entries accepted by `filter` are considered. Trains not departed yet, canceled
+/// or already arrived at their destination are left out.
+pub async fn circulating_trains(
+    client: &ViaggiatrenoClient,
+    station_codes: &[&str],
+    time: DateTime<Tz>,
+    filter: impl Fn(&StationBoardEntry) -> bool,
+) -> Result<Vec<TrainStatus>> {
+    circulating_trains_except(client, station_codes, time, filter, &[]).await
+}
+
+/// Finds the trains currently running through the given stations like [`circulating_trains`],
+/// leaving out the `known` trains, whose status has already been fetched.
+pub async fn circulating_trains_except(
+    client: &ViaggiatrenoClient,
+    station_codes: &[&str],
+    time: DateTime<Tz>,
+    filter: impl Fn(&StationBoardEntry) -> bool,
+    known: &[TrainStatus],
+) -> Result<Vec<TrainStatus>> {
+    let (arrivals, departures) = futures::join!(
+        join_all(station_codes.iter().map(|code| client.arrivals(code, time))),
+        join_all(
+            station_codes
+                .iter()
+                .map(|code| client.departures(code, time))
+        ),
+    );
+    let boards = arrivals.into_iter().chain(departures);
+
+    // The same train is usually listed on several boards
+    let mut seen: HashSet<_> = known
+        .iter()
+        .filter_map(|t| Some((t.number?, t.origin_id.clone()?, t.departure_date?)))
+        .collect();
+    let mut entries = Vec::new();
+    for entry in boards
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+    {
+        if !filter(&entry) {
+            continue;
+        }
+        let (Some(number), Some(origin_id), Some(departure_date)) =
+            (entry.number, entry.origin_id.clone(), entry.departure_date)
+        else {
+            continue;
+        };
+        if seen.insert((number, origin_id.clone(), departure_date)) {
+            entries.push((origin_id, number, departure_date.timestamp_millis()));
+        }
+    }
+
+    let statuses =
+        join_all(entries.iter().map(|(origin_id, number, timestamp)| {
+            client.train_status(origin_id, *number, *timestamp)
+        }))
+        .await;
+
+    // A train whose status cannot be fetched is skipped, unless no status could be fetched at all
+    let mut trains = Vec::new();
+    let mut first_error = None;
+    for status in statuses {
+        match status {
+            Ok(status) => trains.push(status),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if trains.is_empty()
+        && let Some(e) = first_error
+    {
+        return Err(e);
+    }
+
+    trains.retain(|t| !t.is_not_departed() && !t.is_canceled() && !t.is_arrived());
+
+    Ok(trains)
+}
@@ -1,9 +1,393 @@
-use std::io;
-
-pub fn get_index() -> usize {
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Could not read input.");
-    input.trim().parse::<usize>().expect("Invalid input.")
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::str::FromStr;
+
+use colored::Colorize;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, queue};
+use viaggiatreno::{Error, Result};
+
+use crate::context::Context;
+
+/// Candidate to choose when a search returns several results, see the `--pick` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pick {
+    First,
+    /// 1-based index, as shown in the list of candidates
+    Index(usize),
+}
+
+impl FromStr for Pick {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("first") {
+            return Ok(Pick::First);
+        }
+
+        match s.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!(
+                "\"{s}\" is not valid, expected \"first\" or a number starting from 1"
+            )),
+            Ok(n) => Ok(Pick::Index(n)),
+        }
+    }
+}
+
+/// Selects one of the candidates, returning its index.
+/// If there are several candidates and none is picked, the user is prompted to choose one, unless
+/// the CLI is not interactive: then the candidates are listed and an ambiguous error is returned,
+/// with the `hint` on how the command can select one non-interactively.
+pub fn select(
+    ctx: &Context,
+    message: &str,
+    candidates: &[String],
+    pick: Option<Pick>,
+    hint: Option<&str>,
+) -> Result<usize> {
+    match pick {
+        Some(Pick::First) if !candidates.is_empty() => return Ok(0),
+        Some(Pick::Index(n)) if (1..=candidates.len()).contains(&n) => return Ok(n - 1),
+        Some(_) => return Err(Error::InvalidInput("invalid index".to_string())),
+        None if candidates.len() == 1 => return Ok(0),
+        None => {}
+    }
+
+    if !ctx.interactive {
+        eprintln!("{message}");
+        print_candidates(candidates);
+
+        let message = match hint {
+            Some(hint) => format!("{message} {hint}"),
+            None => message.to_string(),
+        };
+        return Err(Error::Ambiguous {
+            message,
+            candidates: candidates.to_vec(),
+        });
+    }
+
+    let message = format!("{message} Please select the desired one:");
+    prompt_selection(&message, candidates)?.ok_or(Error::Canceled)
+}
+
+fn print_candidates(candidates: &[String]) {
+    candidates.iter().enumerate().for_each(|(i, candidate)| {
+        eprintln!("{}. {}", i + 1, candidate.bold());
+    });
+}
+
+/// Asks the user to choose one of the candidates, returning its index, or `None` if the user
+/// cancels the selection.
+/// On terminals supporting it, the candidates can be browsed with the arrow keys; otherwise they
+/// are chosen by number. In both cases typing some text narrows the list to the matching
+/// candidates.
+pub fn prompt_selection(message: &str, candidates: &[String]) -> Result<Option<usize>> {
+    let is_terminal = io::stdin().is_terminal() && io::stderr().is_terminal();
+
+    if is_terminal && let Ok(raw_mode) = RawMode::enable() {
+        return arrow_selection(message, candidates, raw_mode)
+            .map_err(|e| Error::InvalidInput(format!("could not read input: {e}")));
+    }
+
+    line_selection(message, candidates)
+}
+
+/// Indexes of the candidates containing the query, ignoring case.
+fn filter_candidates(candidates: &[String], query: &str) -> Vec<usize> {
+    let query = query.to_lowercase();
+
+    candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.to_lowercase().contains(&query))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn line_selection(message: &str, candidates: &[String]) -> Result<Option<usize>> {
+    read_selection(message, candidates, &mut io::stdin().lock())
+}
+
+/// Reads the selection from `input` one line at a time, printing the prompts on stderr.
+fn read_selection(
+    message: &str,
+    candidates: &[String],
+    input: &mut impl BufRead,
+) -> Result<Option<usize>> {
+    let mut matches: Vec<usize> = (0..candidates.len()).collect();
+
+    eprintln!("{message}");
+
+    loop {
+        for &i in &matches {
+            eprintln!("{}. {}", i + 1, candidates[i].bold());
+        }
+        eprint!(
+            "{}",
+            "Enter a number, some text to filter the list, or q to cancel: ".dimmed()
+        );
+        io::stderr().flush().ok();
+
+        let mut line = String::new();
+        let bytes_read = input
+            .read_line(&mut line)
+            .map_err(|e| Error::InvalidInput(format!("could not read input: {e}")))?;
+
+        // End of input (Ctrl-D)
+        if bytes_read == 0 {
+            eprintln!();
+            return Ok(None);
+        }
+
+        let input = line.trim();
+
+        if input.eq_ignore_ascii_case("q") {
+            return Ok(None);
+        }
+
+        if input.is_empty() {
+            matches = (0..candidates.len()).collect();
+            continue;
+        }
+
+        if let Ok(n) = input.parse::<usize>() {
+            match n.checked_sub(1) {
+                Some(index) if matches.contains(&index) => return Ok(Some(index)),
+                _ => eprintln!("Invalid number."),
+            }
+            continue;
+        }
+
+        let filtered = filter_candidates(candidates, input);
+        if filtered.is_empty() {
+            eprintln!("No match for \"{input}\".");
+        } else {
+            matches = filtered;
+        }
+    }
+}
+
+fn arrow_selection(
+    message: &str,
+    candidates: &[String],
+    _raw_mode: RawMode,
+) -> io::Result<Option<usize>> {
+    const VISIBLE_ROWS: usize = 10;
+    const INSTRUCTIONS: &str = "(↑/↓ to move, type to filter, Enter to select, Esc or q to cancel)";
+
+    let mut stderr = io::stderr();
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut offset = 0;
+    let mut rendered_lines = 0;
+
+    loop {
+        let matches = filter_candidates(candidates, &query);
+        selected = selected.min(matches.len().saturating_sub(1));
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + VISIBLE_ROWS {
+            offset = selected + 1 - VISIBLE_ROWS;
+        }
+
+        let width = terminal::size()
+            .map(|(w, _)| w as usize)
+            .ok()
+            .filter(|&w| w > 0)
+            .unwrap_or(80);
+
+        // Every line is truncated to the terminal width: a wrapped line would take more rows than
+        // the ones cleared on the next redraw
+        let header = truncate(&format!("{message} {INSTRUCTIONS}"), width - 1);
+        let (title, instructions) = header.split_at(message.len().min(header.len()));
+        let mut lines = vec![
+            format!("{}{}", title, instructions.dimmed()),
+            truncate(&format!("> {}", query), width - 1),
+        ];
+        for (i, &index) in matches.iter().enumerate().skip(offset).take(VISIBLE_ROWS) {
+            let candidate = truncate(
+                &format!("{}. {}", index + 1, candidates[index]),
+                width.saturating_sub(3),
+            );
+            if i == selected {
+                lines.push(format!("{} {}", "›".cyan(), candidate.bold()));
+            } else {
+                lines.push(format!("  {}", candidate));
+            }
+        }
+        if matches.is_empty() {
+            lines.push(format!("  {}", "No match.".dimmed()));
+        }
+
+        clear_lines(&mut stderr, rendered_lines)?;
+        // Raw mode does not translate new lines into carriage returns
+        write!(stderr, "{}", lines.join("\r\n"))?;
+        stderr.flush()?;
+        rendered_lines = lines.len() as u16 - 1;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        if is_cancel_key(&key, &query) {
+            clear_lines(&mut stderr, rendered_lines)?;
+            return Ok(None);
+        }
+
+        let is_control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Down if selected + 1 < matches.len() => selected += 1,
+            KeyCode::Enter => {
+                if let Some(&index) = matches.get(selected) {
+                    clear_lines(&mut stderr, rendered_lines)?;
+                    write!(stderr, "{} {}\r\n", message, candidates[index].bold())?;
+                    return Ok(Some(index));
+                }
+            }
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) if !is_control => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The first `width` characters of the text.
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Whether the key cancels the selection: Esc, Ctrl-C, Ctrl-D, or q before any text is typed,
+/// since afterwards it is part of the filter.
+fn is_cancel_key(key: &KeyEvent, query: &str) -> bool {
+    let is_control = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Esc => true,
+        KeyCode::Char('c' | 'd') => is_control,
+        KeyCode::Char('q' | 'Q') => !is_control && query.is_empty(),
+        _ => false,
+    }
+}
+
+/// Clears the lines rendered by the picker, moving the cursor back to where it started.
+fn clear_lines(stderr: &mut io::Stderr, rendered_lines: u16) -> io::Result<()> {
+    if rendered_lines > 0 {
+        queue!(stderr, cursor::MoveUp(rendered_lines))?;
+    }
+    queue!(
+        stderr,
+        cursor::MoveToColumn(0),
+        Clear(ClearType::FromCursorDown)
+    )?;
+    stderr.flush()
+}
+
+/// Enables the terminal raw mode, restoring it when dropped.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<String> {
+        [
+            "BOLOGNA CENTRALE (S05043)",
+            "BOLOGNA SAN RUFFILLO (S05311)",
+            "BOLZANO (S02026)",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn select_with_input(input: &str) -> Option<usize> {
+        read_selection("Select a station:", &candidates(), &mut input.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn filter_candidates_ignores_case() {
+        assert_eq!(filter_candidates(&candidates(), "bologna"), [0, 1]);
+        assert_eq!(filter_candidates(&candidates(), "Ruffillo"), [1]);
+        assert_eq!(filter_candidates(&candidates(), ""), [0, 1, 2]);
+        assert!(filter_candidates(&candidates(), "Roma").is_empty());
+    }
+
+    #[test]
+    fn line_selection_selects_by_number() {
+        assert_eq!(select_with_input("3\n"), Some(2));
+    }
+
+    #[test]
+    fn line_selection_prompts_again_after_invalid_input() {
+        // Out of range, then no match, then valid
+        assert_eq!(select_with_input("4\nRoma\n2\n"), Some(1));
+    }
+
+    #[test]
+    fn line_selection_keeps_numbers_of_filtered_candidates() {
+        assert_eq!(select_with_input("bolz\n3\n"), Some(2));
+        // Candidates filtered out cannot be selected until the filter is cleared
+        assert_eq!(select_with_input("ruffillo\n1\n\n1\n"), Some(0));
+    }
+
+    #[test]
+    fn line_selection_cancels_with_q_or_end_of_input() {
+        assert_eq!(select_with_input("q\n"), None);
+        assert_eq!(select_with_input("Q\n"), None);
+        assert_eq!(select_with_input("bologna\n"), None);
+        assert_eq!(select_with_input(""), None);
+    }
+
+    #[test]
+    fn truncate_keeps_lines_within_the_width() {
+        assert_eq!(
+            truncate("Select a station: (↑/↓ to move)", 20),
+            "Select a station: (↑"
+        );
+        assert_eq!(truncate("BOLZANO", 20), "BOLZANO");
+    }
+
+    #[test]
+    fn q_cancels_the_arrow_selection_before_typing() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+
+        assert!(is_cancel_key(
+            &key(KeyCode::Char('q'), KeyModifiers::NONE),
+            ""
+        ));
+        assert!(!is_cancel_key(
+            &key(KeyCode::Char('q'), KeyModifiers::NONE),
+            "bol"
+        ));
+        assert!(is_cancel_key(&key(KeyCode::Esc, KeyModifiers::NONE), "bol"));
+        assert!(is_cancel_key(
+            &key(KeyCode::Char('d'), KeyModifiers::CONTROL),
+            "bol"
+        ));
+        assert!(!is_cancel_key(
+            &key(KeyCode::Char('d'), KeyModifiers::NONE),
+            ""
+        ));
+    }
 }
@@ -0,0 +1,260 @@
+use chrono::{DateTime, NaiveDateTime};
+use chrono_tz::Tz;
+use scraper::{Html, Selector};
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, Result};
+use crate::models::{StationBoardEntry, TrainStatus, TravelSolutions};
+
+/// Base URL of the public Viaggiatreno API.
+pub const DEFAULT_BASE_URL: &str = "http://www.viaggiatreno.it";
+const API_PATH: &str = "infomobilita/resteasy/viaggiatreno";
+const MOBILE_API_PATH: &str = "infomobilitamobile/resteasy/viaggiatreno";
+
+/// A train returned by the train number autocomplete endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrainCandidate {
+    /// Human readable description, e.g. "9612 - NAPOLI CENTRALE - 18/10/26"
+    pub description: String,
+    /// Code of the origin station of the train (e.g. "S09218")
+    pub origin_id: String,
+    /// Departure date of the train, as milliseconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+/// A station returned by the station autocomplete endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StationCandidate {
+    /// Station name (e.g. "MILANO CENTRALE")
+    pub name: String,
+    /// Station code (e.g. "S01700")
+    pub code: String,
+}
+
+/// An item of the "Notizie Infomobilità" feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewsItem {
+    pub title: String,
+    /// Whether the news item is marked as relevant by Trenitalia
+    pub is_highlighted: bool,
+    pub body: String,
+}
+
+/// Client for the Viaggiatreno API.
+///
+/// Cloning the client is cheap, as the underlying HTTP connection pool is shared.
+#[derive(Debug, Clone)]
+pub struct ViaggiatrenoClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Default for ViaggiatrenoClient {
+    fn default() -> Self {
+        Self::with_base_url(DEFAULT_BASE_URL)
+    }
+}
+
+impl ViaggiatrenoClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a client sending requests to a server other than the public one, such as a caching
+    /// proxy or a local mock server (e.g. "http://localhost:8080").
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn api_url(&self, endpoint: &str) -> String {
+        format!("{}/{}/{}", self.base_url, API_PATH, endpoint)
+    }
+
+    fn mobile_api_url(&self, endpoint: &str) -> String {
+        format!("{}/{}/{}", self.base_url, MOBILE_API_PATH, endpoint)
+    }
+
+    async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        let res = self.http.get(url).send().await?;
+
+        let status = res.status();
+        if !status.is_success() {
+            return Err(Error::Status {
+                status,
+                url: url.to_string(),
+            });
+        }
+
+        Ok(res)
+    }
+
+    async fn get_text(&self, url: &str) -> Result<String> {
+        Ok(self.get(url).await?.text().await?)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let body = self.get(url).await?.bytes().await?;
+        serde_json::from_slice(&body).map_err(|e| Error::MalformedResponse(e.to_string()))
+    }
+
+    /// Finds the trains matching the given train number.
+    pub async fn autocomplete_train(&self, number: u32) -> Result<Vec<TrainCandidate>> {
+        let url = self.api_url(&format!("cercaNumeroTrenoTrenoAutocomplete/{}", number));
+        let res = self.get_text(&url).await?;
+
+        // Each line has the form "9612 - NAPOLI CENTRALE - 18/10/26|9612-S09218-1760738400000"
+        res.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                let malformed_line = || Error::MalformedResponse(format!("unexpected train: {l}"));
+
+                let (description, id) = l.split_once('|').ok_or_else(malformed_line)?;
+                let mut id_sections = id.split('-').skip(1);
+                let origin_id = id_sections.next().ok_or_else(malformed_line)?;
+                let timestamp = id_sections
+                    .next()
+                    .and_then(|t| t.trim().parse().ok())
+                    .ok_or_else(malformed_line)?;
+
+                Ok(TrainCandidate {
+                    description: description.to_string(),
+                    origin_id: origin_id.to_string(),
+                    timestamp,
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the current status of a train, including all its stops.
+    pub async fn train_status(
+        &self,
+        origin_id: &str,
+        number: u32,
+        timestamp: i64,
+    ) -> Result<TrainStatus> {
+        let url = self.api_url(&format!(
+            "andamentoTreno/{}/{}/{}",
+            origin_id, number, timestamp
+        ));
+        self.get_json(&url).await
+    }
+
+    /// Finds the stations whose name begins with the given text.
+    pub async fn autocomplete_station(&self, name: &str) -> Result<Vec<StationCandidate>> {
+        let url = self.api_url(&format!("autocompletaStazione/{}", name.trim()));
+        let res = self.get_text(&url).await?;
+
+        // Each line has the form "MILANO CENTRALE|S01700"
+        res.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                let (name, code) = l
+                    .split_once('|')
+                    .ok_or_else(|| Error::MalformedResponse(format!("unexpected station: {l}")))?;
+
+                Ok(StationCandidate {
+                    name: name.to_string(),
+                    code: code.trim().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the trains arriving at a station around the given time.
+    pub async fn arrivals(
+        &self,
+        station_code: &str,
+        time: DateTime<Tz>,
+    ) -> Result<Vec<StationBoardEntry>> {
+        let url = self.mobile_api_url(&format!(
+            "arrivi/{}/{}",
+            station_code,
+            format_board_time(time)
+        ));
+        self.get_json(&url).await
+    }
+
+    /// Gets the trains departing from a station around the given time.
+    pub async fn departures(
+        &self,
+        station_code: &str,
+        time: DateTime<Tz>,
+    ) -> Result<Vec<StationBoardEntry>> {
+        let url = self.mobile_api_url(&format!(
+            "partenze/{}/{}",
+            station_code,
+            format_board_time(time)
+        ));
+        self.get_json(&url).await
+    }
+
+    /// Finds the travel solutions between two stations, departing after the given time.
+    /// `time` is in the Europe/Rome timezone.
+    pub async fn travel_solutions(
+        &self,
+        from_station_code: &str,
+        to_station_code: &str,
+        time: NaiveDateTime,
+    ) -> Result<TravelSolutions> {
+        let url = self.api_url(&format!(
+            "soluzioniViaggioNew/{}/{}/{}",
+            numeric_station_id(from_station_code),
+            numeric_station_id(to_station_code),
+            time.format("%Y-%m-%dT%H:%M:%S")
+        ));
+        self.get_json(&url).await
+    }
+
+    /// Gets the "Notizie Infomobilità" feed about line disruptions.
+    pub async fn news(&self) -> Result<Vec<NewsItem>> {
+        let url = self.mobile_api_url("infomobilitaRSS/false");
+        let res = self.get_text(&url).await?;
+
+        let fragment = Html::parse_fragment(&res);
+        let selector = Selector::parse("li").unwrap();
+
+        let news = fragment
+            .select(&selector)
+            .map_while(|element| {
+                let mut children_iter = element.child_elements();
+
+                let title_element = children_iter.next()?;
+                let is_highlighted = title_element
+                    .value()
+                    .attr("class")
+                    .unwrap_or_default()
+                    .contains("inEvidenza");
+
+                let body = children_iter
+                    .next()
+                    .map(|e| e.text().collect::<String>().trim().replace('\t', ""))
+                    .unwrap_or_default();
+
+                Some(NewsItem {
+                    title: title_element.inner_html(),
+                    is_highlighted,
+                    body,
+                })
+            })
+            .collect();
+
+        Ok(news)
+    }
+}
+
+/// The travel solutions endpoint identifies stations by the numeric part of their code, without
+/// leading zeros (e.g. "S01700" becomes "1700").
+fn numeric_station_id(station_code: &str) -> &str {
+    station_code.trim_start_matches(['S', '0'])
+}
+
+fn format_board_time(time: DateTime<Tz>) -> String {
+    time.format("%b %d %Y %H:%M:%S").to_string()
+}
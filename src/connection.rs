@@ -0,0 +1,279 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta};
+use chrono_tz::Tz;
+use colored::Colorize;
+use viaggiatreno::{Error, Result, Stop, StopKind, TrainStatus};
+
+use crate::context::Context;
+use crate::output::{self, ConnectionReport};
+use crate::station;
+use crate::track_train::{self, TrackedTrain, TrainId, TrainSelection};
+use crate::watch;
+
+pub async fn connection(
+    ctx: &Context,
+    arriving: &TrainId,
+    departing: &TrainId,
+    at: Option<&str>,
+    min_transfer: i64,
+    watch: Option<Duration>,
+) -> Result<()> {
+    let hint = Some("Give the category of the train too (e.g. \"FR 9612\") to select one.");
+    let arriving = track_train::find_train(ctx, arriving, &TrainSelection::default(), hint).await?;
+    let departing =
+        track_train::find_train(ctx, departing, &TrainSelection::default(), hint).await?;
+    let station_code = match at {
+        Some(name) => Some(station::find_station(ctx, name).await?.code),
+        None => None,
+    };
+
+    match watch {
+        Some(interval) => {
+            watch::run(interval, async || {
+                print_connection(
+                    ctx,
+                    &arriving,
+                    &departing,
+                    station_code.as_deref(),
+                    min_transfer,
+                    watch,
+                )
+                .await
+            })
+            .await
+        }
+        None => {
+            print_connection(
+                ctx,
+                &arriving,
+                &departing,
+                station_code.as_deref(),
+                min_transfer,
+                None,
+            )
+            .await
+        }
+    }
+}
+
+async fn print_connection(
+    ctx: &Context,
+    arriving: &TrackedTrain,
+    departing: &TrackedTrain,
+    station_code: Option<&str>,
+    min_transfer: i64,
+    watch: Option<Duration>,
+) -> Result<()> {
+    let (arriving, departing) = futures::try_join!(
+        ctx.client
+            .train_status(&arriving.origin_id, arriving.code, arriving.timestamp),
+        ctx.client
+            .train_status(&departing.origin_id, departing.code, departing.timestamp),
+    )?;
+
+    let connection = Connection::new(&arriving, &departing, station_code)?;
+    let transfer = connection.transfer_minutes();
+    let makes_it = connection.is_feasible(min_transfer);
+
+    if ctx.output.is_tabular() {
+        return output::print_rows(ctx.output, &[connection.report(min_transfer)]);
+    } else if !ctx.output.is_human() {
+        output::print_document("connection", connection.report(min_transfer));
+        return Ok(());
+    }
+
+    if let Some(interval) = watch {
+        watch::clear_screen(interval);
+    }
+
+    let label = |train: &TrainStatus| train.label.as_deref().unwrap_or("--").trim().to_string();
+
+    println!(
+        "Connection at {}:\n",
+        connection.arrival_stop.station.cyan()
+    );
+    println!(
+        "\t{}  arrival    scheduled {}  estimated {}  platform {}",
+        label(&arriving).bold(),
+        track_train::format_time(connection.arrival_stop.scheduled_arrival),
+        track_train::format_time(connection.estimated_arrival()).bold(),
+        platform(connection.arrival_stop).unwrap_or("--")
+    );
+    println!(
+        "\t{}  departure  scheduled {}  estimated {}  platform {}",
+        label(&departing).bold(),
+        track_train::format_time(connection.departure_stop.scheduled_departure),
+        track_train::format_time(connection.estimated_departure()).bold(),
+        platform(connection.departure_stop).unwrap_or("--")
+    );
+    println!();
+
+    match transfer {
+        Some(minutes) => println!(
+            "Transfer time: {} min (minimum {} min)",
+            minutes, min_transfer
+        ),
+        None => println!("Transfer time: --"),
+    }
+
+    let verdict = if makes_it {
+        "MAKE".bold().green()
+    } else {
+        "MISS".bold().bright_red()
+    };
+    println!("{verdict}");
+    if let Some(reason) = connection.impossibility() {
+        println!("{}", reason.bright_red());
+    }
+
+    Ok(())
+}
+
+/// A change between two trains at a shared stop.
+struct Connection<'a> {
+    arriving: &'a TrainStatus,
+    departing: &'a TrainStatus,
+    arrival_stop: &'a Stop,
+    departure_stop: &'a Stop,
+}
+
+impl<'a> Connection<'a> {
+    /// Finds the stop where the change happens: the given station, or the first stop of the
+    /// arriving train where the departing train stops too and departs from, which excludes its
+    /// terminus.
+    fn new(
+        arriving: &'a TrainStatus,
+        departing: &'a TrainStatus,
+        station_code: Option<&str>,
+    ) -> Result<Self> {
+        let label = |train: &TrainStatus| train.label.as_deref().unwrap_or("--").trim().to_string();
+
+        let (arrival_stop, departure_stop) = match station_code {
+            Some(code) => {
+                let arrival_stop = arriving
+                    .stops
+                    .iter()
+                    .find(|s| s.station_code == code)
+                    .ok_or_else(|| {
+                        Error::NotFound(format!(
+                            "Train {} does not stop at the station.",
+                            label(arriving)
+                        ))
+                    })?;
+                let departure_stop = departing
+                    .stops
+                    .iter()
+                    .find(|s| s.station_code == code)
+                    .ok_or_else(|| {
+                        Error::NotFound(format!(
+                            "Train {} does not stop at the station.",
+                            label(departing)
+                        ))
+                    })?;
+                (arrival_stop, departure_stop)
+            }
+            None => arriving
+                .stops
+                .iter()
+                .skip(1)
+                .find_map(|a| {
+                    departing
+                        .stops
+                        .iter()
+                        .find(|d| {
+                            d.station_code == a.station_code && d.scheduled_departure.is_some()
+                        })
+                        .map(|d| (a, d))
+                })
+                .ok_or_else(|| {
+                    Error::NotFound(format!(
+                        "Trains {} and {} have no stop in common.",
+                        label(arriving),
+                        label(departing)
+                    ))
+                })?,
+        };
+
+        Ok(Self {
+            arriving,
+            departing,
+            arrival_stop,
+            departure_stop,
+        })
+    }
+
+    /// Actual arrival of the arriving train, or the scheduled one shifted by its current delay.
+    fn estimated_arrival(&self) -> Option<DateTime<Tz>> {
+        self.arrival_stop.actual_arrival.or_else(|| {
+            self.arrival_stop
+                .scheduled_arrival
+                .map(|t| t + TimeDelta::minutes(self.arriving.delay.unwrap_or(0)))
+        })
+    }
+
+    /// Actual departure of the departing train, or the scheduled one shifted by its current
+    /// delay. Trains in advance are expected to wait for their scheduled departure.
+    fn estimated_departure(&self) -> Option<DateTime<Tz>> {
+        self.departure_stop.actual_departure.or_else(|| {
+            self.departure_stop
+                .scheduled_departure
+                .map(|t| t + TimeDelta::minutes(self.departing.delay.unwrap_or(0).max(0)))
+        })
+    }
+
+    fn transfer_minutes(&self) -> Option<i64> {
+        Some((self.estimated_departure()? - self.estimated_arrival()?).num_minutes())
+    }
+
+    /// Why the change cannot happen regardless of the times, if it cannot.
+    fn impossibility(&self) -> Option<&'static str> {
+        if self.arriving.is_canceled() || self.arrival_stop.kind == StopKind::Canceled {
+            Some("The arriving train does not stop at the station.")
+        } else if self.departing.is_canceled() || self.departure_stop.kind == StopKind::Canceled {
+            Some("The departing train does not stop at the station.")
+        } else if self.departure_stop.actual_departure.is_some()
+            && self.arrival_stop.actual_arrival.is_none()
+        {
+            Some("The departing train has already left.")
+        } else {
+            None
+        }
+    }
+
+    fn is_feasible(&self, min_transfer: i64) -> bool {
+        self.impossibility().is_none()
+            && self
+                .transfer_minutes()
+                .is_some_and(|minutes| minutes >= min_transfer)
+    }
+
+    fn report(&self, min_transfer: i64) -> ConnectionReport {
+        let label = |train: &TrainStatus| train.label.as_deref().map(|l| l.trim().to_string());
+
+        ConnectionReport {
+            station: self.arrival_stop.station.clone(),
+            station_code: self.arrival_stop.station_code.clone(),
+            arriving_train: label(self.arriving),
+            scheduled_arrival: self.arrival_stop.scheduled_arrival,
+            estimated_arrival: self.estimated_arrival(),
+            arrival_platform: platform(self.arrival_stop).map(str::to_string),
+            departing_train: label(self.departing),
+            scheduled_departure: self.departure_stop.scheduled_departure,
+            estimated_departure: self.estimated_departure(),
+            departure_platform: platform(self.departure_stop).map(str::to_string),
+            transfer: self.transfer_minutes(),
+            min_transfer,
+            verdict: if self.is_feasible(min_transfer) {
+                "make"
+            } else {
+                "miss"
+            },
+        }
+    }
+}
+
+/// Actual platform of a stop, or the scheduled one.
+fn platform(stop: &Stop) -> Option<&str> {
+    stop.actual_platform().or(stop.scheduled_platform())
+}
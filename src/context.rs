@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use viaggiatreno::ViaggiatrenoClient;
+
+use crate::history::HistoryStore;
+use crate::output::OutputFormat;
+
+/// Global options shared by all the commands.
+pub struct Context {
+    pub client: ViaggiatrenoClient,
+    pub output: OutputFormat,
+    /// Whether the user can be prompted for input
+    pub interactive: bool,
+    /// Store of the delay history, `None` if recording is disabled
+    pub history: Option<HistoryStore>,
+}
+
+/// Directory of the files kept by the program, which can be set with the `INFOTRENO_DATA_DIR`
+/// environment variable.
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os("INFOTRENO_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_dir().map(|d| d.join("infotreno-cli")))
+}
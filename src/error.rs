@@ -0,0 +1,88 @@
+use thiserror::Error;
+
+/// Errors returned by the Viaggiatreno client and by the CLI commands.
+///
+/// Each variant maps to a distinct process exit code, see [`Error::exit_code`].
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The request could not be sent or the response could not be received
+    #[error("cannot complete request: {0}")]
+    Network(#[source] reqwest::Error),
+    /// The server responded with an unsuccessful HTTP status
+    #[error("the server responded with status {status} for {url}")]
+    Status {
+        status: reqwest::StatusCode,
+        url: String,
+    },
+    /// The response body does not have the expected format
+    #[error("malformed response: {0}")]
+    MalformedResponse(String),
+    /// No train or station matches the search
+    #[error("{0}")]
+    NotFound(String),
+    /// Several candidates match the search and none could be selected
+    #[error("{message}")]
+    Ambiguous {
+        message: String,
+        candidates: Vec<String>,
+    },
+    /// The user input is not valid
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    /// A local file, such as the delay history, could not be read or written
+    #[error("{message}: {source}")]
+    Io {
+        message: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The user canceled an interactive selection
+    #[error("canceled")]
+    Canceled,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Process exit code associated with the error:
+    ///
+    /// | Code | Error                 |
+    /// |------|-----------------------|
+    /// | 2    | invalid input         |
+    /// | 3    | not found             |
+    /// | 4    | ambiguous selection   |
+    /// | 5    | network error         |
+    /// | 6    | HTTP status error     |
+    /// | 7    | malformed response    |
+    /// | 8    | local file error      |
+    /// | 130  | canceled by the user  |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidInput(_) => 2,
+            Error::NotFound(_) => 3,
+            Error::Ambiguous { .. } => 4,
+            Error::Network(_) => 5,
+            Error::Status { .. } => 6,
+            Error::MalformedResponse(_) => 7,
+            Error::Io { .. } => 8,
+            Error::Canceled => 130,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_builder() {
+            Error::InvalidInput(error.to_string())
+        } else if error.is_decode() {
+            Error::MalformedResponse(error.to_string())
+        } else if let Some(status) = error.status() {
+            Error::Status {
+                status,
+                url: error.url().map(|u| u.to_string()).unwrap_or_default(),
+            }
+        } else {
+            Error::Network(error)
+        }
+    }
+}
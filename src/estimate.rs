@@ -0,0 +1,124 @@
+//! Estimated times of a train at the stops it has not reached yet.
+//!
+//! The naive estimate adds the current delay to every scheduled time. Timetables however include
+//! some slack: recovery margins on the running time between stops, and dwell times longer than
+//! the time actually needed to board. A late train uses that slack to make up part of its delay,
+//! which [`RecoveryModel`] takes into account.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta};
+use chrono_tz::Tz;
+
+use crate::models::{Stop, StopKind, TrainStatus};
+
+/// Estimated times and delays of a train at a stop, with the delay in minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopEstimate {
+    pub arrival: Option<DateTime<Tz>>,
+    pub departure: Option<DateTime<Tz>>,
+    pub arrival_delay: i64,
+    pub departure_delay: i64,
+}
+
+impl StopEstimate {
+    fn new(stop: &Stop, arrival_delay: f64, departure_delay: f64) -> Self {
+        let arrival_delay = arrival_delay.round() as i64;
+        let departure_delay = departure_delay.round() as i64;
+
+        Self {
+            arrival: stop
+                .scheduled_arrival
+                .map(|t| t + TimeDelta::minutes(arrival_delay)),
+            departure: stop
+                .scheduled_departure
+                .map(|t| t + TimeDelta::minutes(departure_delay)),
+            arrival_delay,
+            departure_delay,
+        }
+    }
+}
+
+/// How much delay a train is expected to make up along its run.
+#[derive(Debug, Clone)]
+pub struct RecoveryModel {
+    /// Fraction of the scheduled running time between two stops that can be recovered
+    pub recovery_rate: f64,
+    /// Minimum time, in minutes, a train stays at a stop; the rest of the scheduled dwell time
+    /// can be recovered
+    pub min_dwell: i64,
+    /// Delay recovered on a segment, in minutes, keyed by the codes of its stations. It replaces
+    /// the recovery computed with [`RecoveryModel::recovery_rate`], and may be negative when the
+    /// train usually loses time on the segment.
+    pub segment_recovery: HashMap<(String, String), f64>,
+}
+
+impl Default for RecoveryModel {
+    fn default() -> Self {
+        Self {
+            // Italian timetables add a regularity margin of a few percent of the running time,
+            // which a late train does not always manage to use entirely
+            recovery_rate: 0.02,
+            min_dwell: 1,
+            segment_recovery: HashMap::new(),
+        }
+    }
+}
+
+impl RecoveryModel {
+    /// Estimates the times of the train at each stop, starting from its current delay.
+    /// Stops already reached or canceled have no estimate.
+    pub fn estimate(&self, status: &TrainStatus) -> Vec<Option<StopEstimate>> {
+        let stops = &status.stops;
+        let mut estimates = vec![None; stops.len()];
+
+        let start = status
+            .last_reached_stop_index()
+            .map_or(0, |index| index + 1);
+        let mut previous = start.checked_sub(1).map(|index| &stops[index]);
+        let mut delay = status.delay.unwrap_or(0) as f64;
+
+        for (index, stop) in stops.iter().enumerate().skip(start) {
+            if stop.kind != StopKind::Pending {
+                continue;
+            }
+
+            let arrival_delay = match previous {
+                // A train in advance is not expected to gain more time
+                Some(previous) if delay > 0.0 => (delay - self.recovery(previous, stop)).max(0.0),
+                _ => delay,
+            };
+            let departure_delay =
+                match minutes_between(stop.scheduled_arrival, stop.scheduled_departure) {
+                    Some(dwell) => {
+                        let slack = (dwell - self.min_dwell).max(0) as f64;
+                        (arrival_delay - slack).max(0.0)
+                    }
+                    // Trains do not leave their origin in advance
+                    None => arrival_delay.max(0.0),
+                };
+
+            estimates[index] = Some(StopEstimate::new(stop, arrival_delay, departure_delay));
+
+            delay = departure_delay;
+            previous = Some(stop);
+        }
+
+        estimates
+    }
+
+    /// Delay recovered between two consecutive stops, in minutes.
+    fn recovery(&self, from: &Stop, to: &Stop) -> f64 {
+        let key = (from.station_code.clone(), to.station_code.clone());
+        if let Some(recovery) = self.segment_recovery.get(&key) {
+            return *recovery;
+        }
+
+        minutes_between(from.scheduled_departure, to.scheduled_arrival)
+            .map_or(0.0, |running| running.max(0) as f64 * self.recovery_rate)
+    }
+}
+
+fn minutes_between(from: Option<DateTime<Tz>>, to: Option<DateTime<Tz>>) -> Option<i64> {
+    Some((to? - from?).num_minutes())
+}
@@ -0,0 +1,203 @@
+//! Changes in the status of a train between two consecutive snapshots.
+
+use std::fmt;
+
+use crate::models::{StopKind, TrainStatus};
+
+/// A change in the status of a train, found by comparing two snapshots with [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrainEvent {
+    /// The train left its origin
+    Departed,
+    /// The train reached an intermediate stop
+    ArrivedAtStop { station: String },
+    /// The train reached its destination
+    ArrivedAtDestination { station: String },
+    /// The delay changed by at least [`DiffOptions::delay_change`] minutes
+    DelayChanged { from: i64, to: i64 },
+    /// The delay reached one of the [`DiffOptions::delay_thresholds`]
+    DelayThresholdCrossed { threshold: i64, delay: i64 },
+    /// A platform was assigned to a stop with no scheduled platform
+    PlatformAssigned { station: String, platform: String },
+    /// The platform of a stop changed, either from the scheduled one or from a previous change
+    PlatformChanged {
+        station: String,
+        from: String,
+        to: String,
+    },
+    /// The whole train has been canceled
+    Canceled,
+    /// A stop has been canceled or removed from the itinerary
+    StopSkipped { station: String },
+}
+
+impl TrainEvent {
+    /// Short identifier of the kind of event (e.g. "platform_changed").
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Departed => "departed",
+            Self::ArrivedAtStop { .. } => "arrived_at_stop",
+            Self::ArrivedAtDestination { .. } => "arrived_at_destination",
+            Self::DelayChanged { .. } => "delay_changed",
+            Self::DelayThresholdCrossed { .. } => "delay_threshold_crossed",
+            Self::PlatformAssigned { .. } => "platform_assigned",
+            Self::PlatformChanged { .. } => "platform_changed",
+            Self::Canceled => "canceled",
+            Self::StopSkipped { .. } => "stop_skipped",
+        }
+    }
+
+    /// Station the event refers to, if any.
+    pub fn station(&self) -> Option<&str> {
+        match self {
+            Self::ArrivedAtStop { station }
+            | Self::ArrivedAtDestination { station }
+            | Self::PlatformAssigned { station, .. }
+            | Self::PlatformChanged { station, .. }
+            | Self::StopSkipped { station } => Some(station),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TrainEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Departed => write!(f, "Train departed"),
+            Self::ArrivedAtStop { station } => write!(f, "Arrived at {station}"),
+            Self::ArrivedAtDestination { station } => {
+                write!(f, "Arrived at destination, {station}")
+            }
+            Self::DelayChanged { from, to } => {
+                write!(f, "Delay changed from {from} to {to} minutes")
+            }
+            Self::DelayThresholdCrossed { threshold, delay } => {
+                write!(f, "Delay reached {threshold} minutes (now {delay})")
+            }
+            Self::PlatformAssigned { station, platform } => {
+                write!(f, "Platform {platform} assigned at {station}")
+            }
+            Self::PlatformChanged { station, from, to } => {
+                write!(f, "Platform at {station} changed from {from} to {to}")
+            }
+            Self::Canceled => write!(f, "Train canceled"),
+            Self::StopSkipped { station } => write!(f, "Stop at {station} skipped"),
+        }
+    }
+}
+
+/// Which delay changes are reported by [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Minimum delay change, in minutes, reported as [`TrainEvent::DelayChanged`]
+    pub delay_change: i64,
+    /// Delays, in minutes, reported as [`TrainEvent::DelayThresholdCrossed`] when reached
+    pub delay_thresholds: Vec<i64>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            delay_change: 5,
+            delay_thresholds: Vec::new(),
+        }
+    }
+}
+
+/// Finds the changes between two snapshots of the same train.
+pub fn diff(
+    previous: &TrainStatus,
+    current: &TrainStatus,
+    options: &DiffOptions,
+) -> Vec<TrainEvent> {
+    let mut events = Vec::new();
+
+    if current.is_canceled() && !previous.is_canceled() {
+        events.push(TrainEvent::Canceled);
+    }
+
+    if previous.is_not_departed() && !current.is_not_departed() {
+        events.push(TrainEvent::Departed);
+    }
+
+    if let (Some(from), Some(to)) = (previous.delay, current.delay) {
+        if (to - from).abs() >= options.delay_change.max(1) {
+            events.push(TrainEvent::DelayChanged { from, to });
+        }
+        for &threshold in &options.delay_thresholds {
+            if from < threshold && to >= threshold {
+                events.push(TrainEvent::DelayThresholdCrossed {
+                    threshold,
+                    delay: to,
+                });
+            }
+        }
+    }
+
+    let last_index = current.stops.len().saturating_sub(1);
+    for (index, current_stop) in current.stops.iter().enumerate() {
+        let Some(previous_stop) = previous
+            .stops
+            .iter()
+            .find(|s| s.station_code == current_stop.station_code)
+        else {
+            continue;
+        };
+
+        if current_stop.kind == StopKind::Canceled && previous_stop.kind != StopKind::Canceled {
+            events.push(TrainEvent::StopSkipped {
+                station: current_stop.station.clone(),
+            });
+            continue;
+        }
+
+        // The origin is left rather than reached
+        if index > 0
+            && previous_stop.kind == StopKind::Pending
+            && matches!(
+                current_stop.kind,
+                StopKind::Regular | StopKind::Extraordinary
+            )
+        {
+            let station = current_stop.station.clone();
+            events.push(if index == last_index {
+                TrainEvent::ArrivedAtDestination { station }
+            } else {
+                TrainEvent::ArrivedAtStop { station }
+            });
+        }
+
+        // The actual platform is compared with the previous actual one, or with the scheduled
+        // one if it was not assigned yet
+        let previous_platform = previous_stop
+            .actual_platform()
+            .or(previous_stop.scheduled_platform());
+        match (previous_platform, current_stop.actual_platform()) {
+            (None, Some(platform)) => events.push(TrainEvent::PlatformAssigned {
+                station: current_stop.station.clone(),
+                platform: platform.to_string(),
+            }),
+            (Some(from), Some(to)) if from != to => events.push(TrainEvent::PlatformChanged {
+                station: current_stop.station.clone(),
+                from: from.to_string(),
+                to: to.to_string(),
+            }),
+            _ => {}
+        }
+    }
+
+    // Stops removed from the itinerary
+    for previous_stop in &previous.stops {
+        if !current
+            .stops
+            .iter()
+            .any(|s| s.station_code == previous_stop.station_code)
+        {
+            events.push(TrainEvent::StopSkipped {
+                station: previous_stop.station.clone(),
+            });
+        }
+    }
+
+    events
+}
@@ -0,0 +1,500 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::{Europe::Rome, Tz};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use tabular::{Table, row};
+use viaggiatreno::{
+    Error, Result, StationBoardEntry, StationCandidate, Stop, StopKind, TrainStatus,
+};
+
+use crate::cli_input;
+use crate::context::{self, Context};
+use crate::output::{self, OutputFormat};
+
+/// Maximum delay, in minutes, for a train to be considered on time.
+/// This is the same threshold used by Trenitalia for its punctuality statistics.
+pub const ON_TIME_THRESHOLD: i64 = 5;
+
+/// Delays and times of a train at a station, on a certain day.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub train_number: u32,
+    pub train_label: String,
+    /// Departure date of the train from its origin
+    pub date: NaiveDate,
+    pub station_code: String,
+    pub station: String,
+    pub scheduled_arrival: Option<DateTime<Utc>>,
+    pub actual_arrival: Option<DateTime<Utc>>,
+    /// Arrival delay in minutes
+    pub arrival_delay: Option<i64>,
+    pub scheduled_departure: Option<DateTime<Utc>>,
+    pub actual_departure: Option<DateTime<Utc>>,
+    /// Departure delay in minutes
+    pub departure_delay: Option<i64>,
+    /// Whether the delays were read from a station board rather than from the actual times of
+    /// the train
+    #[serde(default)]
+    pub estimated: bool,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl Observation {
+    fn key(&self) -> (u32, NaiveDate, String) {
+        (self.train_number, self.date, self.station_code.clone())
+    }
+
+    /// Whether merging the newer observation would leave the times and delays unchanged.
+    fn already_has(&self, newer: &Observation) -> bool {
+        let mut merged = self.clone();
+        merged.merge(newer.clone());
+        merged.recorded_at = self.recorded_at;
+        merged == *self
+    }
+
+    /// Delay at the station: the arrival delay, or the departure delay at the origin.
+    pub fn delay(&self) -> Option<i64> {
+        self.arrival_delay.or(self.departure_delay)
+    }
+
+    /// Overwrites the fields known by the newer observation of the same train, day and station.
+    /// Estimates from a station board only fill the fields still missing, without replacing the
+    /// actual times recorded while tracking the train.
+    fn merge(&mut self, newer: Observation) {
+        fn update<T>(field: &mut Option<T>, value: Option<T>, replace: bool) {
+            if value.is_some() && (replace || field.is_none()) {
+                *field = value;
+            }
+        }
+
+        let replace = self.estimated || !newer.estimated;
+        update(
+            &mut self.scheduled_arrival,
+            newer.scheduled_arrival,
+            replace,
+        );
+        update(&mut self.actual_arrival, newer.actual_arrival, replace);
+        update(&mut self.arrival_delay, newer.arrival_delay, replace);
+        update(
+            &mut self.scheduled_departure,
+            newer.scheduled_departure,
+            replace,
+        );
+        update(&mut self.actual_departure, newer.actual_departure, replace);
+        update(&mut self.departure_delay, newer.departure_delay, replace);
+        self.estimated &= newer.estimated;
+        self.recorded_at = newer.recorded_at;
+    }
+}
+
+/// Local store of the train observations made while running `track` and `station`.
+/// Observations are appended to a JSON lines file in the data directory.
+pub struct HistoryStore {
+    path: PathBuf,
+    /// Observations recorded for each train, day and station, merged, so that refreshes in watch
+    /// mode only append the observations that changed
+    recorded: Mutex<HashMap<(u32, NaiveDate, String), Observation>>,
+}
+
+impl HistoryStore {
+    pub fn open_default() -> Option<Self> {
+        let dir = context::data_dir()?;
+
+        Some(Self {
+            path: dir.join("history.jsonl"),
+            recorded: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Appends the observations, skipping the ones that add nothing to what was already recorded.
+    pub fn record(&self, observations: &[Observation]) -> io::Result<()> {
+        let mut recorded = self.recorded.lock().unwrap();
+        let observations: Vec<_> = observations
+            .iter()
+            .filter(|o| !recorded.get(&o.key()).is_some_and(|r| r.already_has(o)))
+            .collect();
+        if observations.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        for observation in observations {
+            writeln!(file, "{}", serde_json::to_string(observation)?)?;
+            recorded
+                .entry(observation.key())
+                .and_modify(|r| r.merge(observation.clone()))
+                .or_insert_with(|| observation.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Loads the observations of a train, merging the ones of the same day and station.
+    pub fn load(&self, train_number: u32) -> io::Result<Vec<Observation>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut observations: BTreeMap<(NaiveDate, String), Observation> = BTreeMap::new();
+
+        for line in BufReader::new(file).lines() {
+            // Lines that cannot be parsed, e.g. because of an interrupted write, are skipped
+            let Ok(observation) = serde_json::from_str::<Observation>(&line?) else {
+                continue;
+            };
+            if observation.train_number != train_number {
+                continue;
+            }
+
+            let key = (observation.date, observation.station_code.clone());
+            match observations.get_mut(&key) {
+                Some(existing) => existing.merge(observation),
+                None => {
+                    observations.insert(key, observation);
+                }
+            }
+        }
+
+        Ok(observations.into_values().collect())
+    }
+}
+
+/// Records the times of the stops already reached by the train.
+pub fn record_train(ctx: &Context, status: &TrainStatus) {
+    let Some(store) = &ctx.history else {
+        return;
+    };
+    let (Some(train_number), Some(date)) = (status.number, status.departure_date) else {
+        return;
+    };
+
+    let recorded_at = Utc::now();
+    let observations: Vec<_> = status
+        .stops
+        .iter()
+        .filter(|stop| matches!(stop.kind, StopKind::Regular | StopKind::Extraordinary))
+        .map(|stop| Observation {
+            train_number,
+            train_label: status
+                .label
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            date: date.date_naive(),
+            station_code: stop.station_code.clone(),
+            station: stop.station.clone(),
+            scheduled_arrival: to_utc(stop.scheduled_arrival),
+            actual_arrival: to_utc(stop.actual_arrival),
+            arrival_delay: delay_minutes(stop.scheduled_arrival, stop.actual_arrival),
+            scheduled_departure: to_utc(stop.scheduled_departure),
+            actual_departure: to_utc(stop.actual_departure),
+            departure_delay: delay_minutes(stop.scheduled_departure, stop.actual_departure),
+            estimated: false,
+            recorded_at,
+        })
+        .collect();
+
+    save(store, &observations);
+}
+
+/// Records the current delays of the trains on a station board whose scheduled time has passed.
+/// These are only estimates, which get replaced by the actual times when the train is tracked;
+/// trains yet to come are skipped, as their delay may still change.
+pub fn record_board(
+    ctx: &Context,
+    station: &StationCandidate,
+    arrivals: &[StationBoardEntry],
+    departures: &[StationBoardEntry],
+) {
+    let Some(store) = &ctx.history else {
+        return;
+    };
+
+    let recorded_at = Utc::now();
+    let observation = |entry: &StationBoardEntry| {
+        Some(Observation {
+            train_number: entry.number?,
+            train_label: entry.label.trim().to_string(),
+            date: entry.departure_date?.date_naive(),
+            station_code: station.code.clone(),
+            station: station.name.clone(),
+            estimated: true,
+            recorded_at,
+            ..Default::default()
+        })
+    };
+    let has_passed = |scheduled: Option<DateTime<Tz>>| scheduled.is_some_and(|t| t <= recorded_at);
+
+    let arrivals = arrivals.iter().filter_map(|entry| {
+        if !has_passed(entry.scheduled_arrival) {
+            return None;
+        }
+        observation(entry).map(|o| Observation {
+            scheduled_arrival: to_utc(entry.scheduled_arrival),
+            arrival_delay: entry.delay,
+            ..o
+        })
+    });
+    let departures = departures.iter().filter_map(|entry| {
+        if !has_passed(entry.scheduled_departure) {
+            return None;
+        }
+        observation(entry).map(|o| Observation {
+            scheduled_departure: to_utc(entry.scheduled_departure),
+            departure_delay: entry.delay,
+            ..o
+        })
+    });
+
+    save(store, &arrivals.chain(departures).collect::<Vec<_>>());
+}
+
+fn save(store: &HistoryStore, observations: &[Observation]) {
+    if let Err(e) = store.record(observations) {
+        eprintln!(
+            "{}",
+            format!("Could not record delay history: {e}").dimmed()
+        );
+    }
+}
+
+fn delay_minutes(scheduled: Option<DateTime<Tz>>, actual: Option<DateTime<Tz>>) -> Option<i64> {
+    Some((actual? - scheduled?).num_minutes())
+}
+
+/// Observations are stored in UTC, which unlike the Europe/Rome timezone can be deserialized.
+fn to_utc(time: Option<DateTime<Tz>>) -> Option<DateTime<Utc>> {
+    time.map(|t| t.to_utc())
+}
+
+/// Minimum number of days a segment must have been observed on to learn its recovery.
+const MIN_RECOVERY_DAYS: usize = 3;
+
+/// Delay the train usually recovers between each pair of consecutive stops, in minutes: the mean
+/// difference between the departure delay from the first stop and the arrival delay at the second
+/// one, over the days both were recorded. Estimates from station boards are not precise enough
+/// and are left out.
+pub fn segment_recovery(
+    observations: &[Observation],
+    stops: &[Stop],
+) -> Vec<(String, String, f64)> {
+    let by_day: HashMap<(NaiveDate, &str), &Observation> = observations
+        .iter()
+        .filter(|o| !o.estimated)
+        .map(|o| ((o.date, o.station_code.as_str()), o))
+        .collect();
+    let days: BTreeSet<NaiveDate> = observations.iter().map(|o| o.date).collect();
+
+    stops
+        .windows(2)
+        .filter_map(|segment| {
+            let (from, to) = (&segment[0].station_code, &segment[1].station_code);
+            let recovered: Vec<i64> = days
+                .iter()
+                .filter_map(|&day| {
+                    let departure_delay = by_day.get(&(day, from.as_str()))?.departure_delay?;
+                    let arrival_delay = by_day.get(&(day, to.as_str()))?.arrival_delay?;
+                    Some(departure_delay - arrival_delay)
+                })
+                .collect();
+
+            (recovered.len() >= MIN_RECOVERY_DAYS).then(|| {
+                let mean = recovered.iter().sum::<i64>() as f64 / recovered.len() as f64;
+                (from.clone(), to.clone(), mean)
+            })
+        })
+        .collect()
+}
+
+/// Summary statistics of the delays of a train at a station, in minutes.
+#[derive(Debug, Serialize)]
+pub struct DelayStats {
+    pub days: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p90: i64,
+    /// Percentage of days with a delay of at most 5 minutes
+    pub on_time_percentage: f64,
+}
+
+impl DelayStats {
+    pub fn new(delays: &[i64]) -> Option<Self> {
+        if delays.is_empty() {
+            return None;
+        }
+
+        let mut delays = delays.to_vec();
+        delays.sort_unstable();
+        let n = delays.len();
+
+        let median = if n.is_multiple_of(2) {
+            (delays[n / 2 - 1] + delays[n / 2]) as f64 / 2.0
+        } else {
+            delays[n / 2] as f64
+        };
+        // Nearest-rank percentile
+        let p90 = delays[(n * 9).div_ceil(10) - 1];
+        let on_time = delays.iter().filter(|&&d| d <= ON_TIME_THRESHOLD).count();
+
+        Some(Self {
+            days: n,
+            mean: delays.iter().sum::<i64>() as f64 / n as f64,
+            median,
+            p90,
+            on_time_percentage: on_time as f64 * 100.0 / n as f64,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct HistoryReport<'a> {
+    train_number: u32,
+    station_code: &'a str,
+    station: &'a str,
+    days: &'a [Observation],
+    stats: Option<DelayStats>,
+}
+
+pub fn history(ctx: &Context, train_number: u32, station: &str) -> Result<()> {
+    let store = ctx.history.as_ref().ok_or_else(|| {
+        Error::NotFound("No data directory available for the delay history.".to_string())
+    })?;
+    let observations = store.load(train_number).map_err(|source| Error::Io {
+        message: "could not read the delay history".to_string(),
+        source,
+    })?;
+
+    // Stations of the train matching the name or code given, in order of appearance
+    let query = station.trim().to_lowercase();
+    let mut stations: Vec<(&str, &str)> = Vec::new();
+    for o in &observations {
+        let matches = o.station.to_lowercase().contains(&query)
+            || o.station_code.eq_ignore_ascii_case(&query);
+        if matches && !stations.iter().any(|(code, _)| *code == o.station_code) {
+            stations.push((&o.station_code, &o.station));
+        }
+    }
+
+    if stations.is_empty() {
+        return Err(Error::NotFound(format!(
+            "No recorded history for train {} at \"{}\". History is recorded when running track or station.",
+            train_number,
+            station.trim()
+        )));
+    }
+
+    let candidates: Vec<_> = stations
+        .iter()
+        .map(|(code, name)| format!("{name} ({code})"))
+        .collect();
+    let index = cli_input::select(
+        ctx,
+        &format!(
+            "Found more than one station matching \"{}\".",
+            station.trim()
+        ),
+        &candidates,
+        None,
+        Some("Use the station code to select one non-interactively."),
+    )?;
+    let (station_code, station_name) = stations[index];
+
+    let days: Vec<_> = observations
+        .iter()
+        .filter(|o| o.station_code == station_code)
+        .cloned()
+        .collect();
+    let delays: Vec<_> = days.iter().filter_map(Observation::delay).collect();
+    let stats = DelayStats::new(&delays);
+
+    if ctx.output.is_tabular() {
+        return output::print_rows(ctx.output, &days);
+    } else if !ctx.output.is_human() {
+        let report = HistoryReport {
+            train_number,
+            station_code,
+            station: station_name,
+            days: &days,
+            stats,
+        };
+        if ctx.output == OutputFormat::Ndjson {
+            days.iter()
+                .for_each(|day| output::print_document("observation", day));
+        } else {
+            output::print_document("delay_history", report);
+        }
+        return Ok(());
+    }
+
+    let train_label = days
+        .last()
+        .map(|o| o.train_label.as_str())
+        .unwrap_or_default();
+    println!(
+        "Delay history of train {} at {}\n",
+        train_label.bold(),
+        station_name.cyan()
+    );
+
+    let mut table = Table::new("{:<}  {:>} {:>} {:>}  {:>} {:>} {:>}");
+    table.add_row(row!(
+        "Date".bold(),
+        "Arrival".bold(),
+        "actual".bold(),
+        "delay".bold(),
+        "Departure".bold(),
+        "actual".bold(),
+        "delay".bold()
+    ));
+    for day in &days {
+        table.add_row(row!(
+            day.date.format("%a %d/%m/%Y"),
+            format_time(day.scheduled_arrival),
+            format_time(day.actual_arrival),
+            format_delay(day.arrival_delay),
+            format_time(day.scheduled_departure),
+            format_time(day.actual_departure),
+            format_delay(day.departure_delay)
+        ));
+    }
+    println!("{table}");
+
+    if let Some(stats) = stats {
+        println!(
+            "Days: {}  Mean delay: {:.1} min  Median: {:.1} min  90th percentile: {} min  On time: {:.0}%",
+            stats.days, stats.mean, stats.median, stats.p90, stats.on_time_percentage
+        );
+    }
+
+    Ok(())
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|t| t.with_timezone(&Rome).format("%H:%M").to_string())
+        .unwrap_or("--:--".to_string())
+}
+
+fn format_delay(delay: Option<i64>) -> String {
+    match delay {
+        None => "".to_string(),
+        Some(n) if n > 0 => format!("+{}", n),
+        Some(n) => n.to_string(),
+    }
+}
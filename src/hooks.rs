@@ -0,0 +1,172 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use colored::Colorize;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use viaggiatreno::{Error, Result};
+
+use crate::output::{self, TrainEventReport};
+
+/// Delay before the first retry of a failed webhook request, doubled at each further retry.
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// A URL receiving the events of a watched train as JSON POST requests.
+/// Events are posted in order by a background task, so that a slow or unreachable URL does not
+/// delay the refresh.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    queue: mpsc::UnboundedSender<String>,
+}
+
+impl Webhook {
+    /// `timeout` applies to each request; failed requests are retried up to `retries` times.
+    pub fn new(url: &str, timeout: Duration, retries: u32) -> Result<Self> {
+        let url = reqwest::Url::parse(url)
+            .map_err(|e| Error::InvalidInput(format!("Invalid webhook URL \"{url}\": {e}")))?;
+        let http = reqwest::Client::builder().timeout(timeout).build()?;
+
+        let (queue, mut documents) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(document) = documents.recv().await {
+                post(&http, &url, retries, document).await;
+            }
+        });
+
+        Ok(Self { queue })
+    }
+
+    /// Queues an event to be posted.
+    pub fn send(&self, event: &TrainEventReport) {
+        // The task only stops when every sender is dropped
+        let _ = self.queue.send(output::to_document("train_event", event));
+    }
+}
+
+/// Posts a document, retrying on network errors and server errors.
+/// Failures are reported without stopping the watch.
+async fn post(http: &reqwest::Client, url: &reqwest::Url, retries: u32, document: String) {
+    let mut retry_delay = WEBHOOK_RETRY_DELAY;
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            tokio::time::sleep(retry_delay).await;
+            retry_delay *= 2;
+        }
+
+        let res = http
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(document.clone())
+            .send()
+            .await;
+
+        let error = match res {
+            Ok(res) if res.status().is_success() => return,
+            Ok(res) => {
+                let status = res.status();
+                // Client errors are not going to be solved by retrying
+                let retry =
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                if !retry {
+                    report_webhook_failure(&format!("unsuccessful status {status}"));
+                    return;
+                }
+                format!("unsuccessful status {status}")
+            }
+            Err(e) => e.to_string(),
+        };
+
+        if attempt == retries {
+            report_webhook_failure(&error);
+        }
+    }
+}
+
+fn report_webhook_failure(error: &str) {
+    eprintln!(
+        "{}",
+        format!("Could not send the event to the webhook: {error}").dimmed()
+    );
+}
+
+/// Runs the `--on-event` command for an event, through the shell.
+/// The event is passed both as a JSON document on the standard input and as `INFOTRENO_*`
+/// environment variables. The command runs in the background, so that a slow command does not
+/// delay the refresh; failures are reported without stopping the watch.
+pub fn run_event_command(command: &str, event: &TrainEventReport) {
+    if let Err(e) = spawn_event_command(command, event) {
+        eprintln!(
+            "{}",
+            format!("Could not run the event command: {e}").dimmed()
+        );
+    }
+}
+
+fn spawn_event_command(command: &str, event: &TrainEventReport) -> std::io::Result<()> {
+    let mut child = shell_command(command)
+        .env("INFOTRENO_EVENT", event.event)
+        .env("INFOTRENO_MESSAGE", &event.message)
+        .env(
+            "INFOTRENO_TRAIN_NUMBER",
+            event
+                .train_number
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+        )
+        .env(
+            "INFOTRENO_TRAIN",
+            event.train_label.as_deref().unwrap_or_default(),
+        )
+        .env(
+            "INFOTRENO_STATION",
+            event.station.as_deref().unwrap_or_default(),
+        )
+        .env(
+            "INFOTRENO_DELAY",
+            event.delay.map(|d| d.to_string()).unwrap_or_default(),
+        )
+        .env(
+            "INFOTRENO_PLATFORM",
+            event.platform.as_deref().unwrap_or_default(),
+        )
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    let document = output::to_document("train_event", event);
+    tokio::spawn(async move {
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command may exit without reading its input
+            let _ = stdin.write_all(document.as_bytes()).await;
+            let _ = stdin.write_all(b"\n").await;
+        }
+
+        match child.wait().await {
+            Ok(status) if !status.success() => eprintln!(
+                "{}",
+                format!("The event command exited with {status}").dimmed()
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "{}",
+                format!("Could not run the event command: {e}").dimmed()
+            ),
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
@@ -0,0 +1,22 @@
+//! Async client for the Viaggiatreno API, the public train information service of Trenitalia.
+//!
+//! The [`ViaggiatrenoClient`] wraps every endpoint used by the `infotreno-cli` binary and returns
+//! plain data, leaving any rendering to the caller.
+
+pub mod circulation;
+mod client;
+mod error;
+pub mod estimate;
+pub mod events;
+pub mod models;
+pub mod time;
+
+pub use client::{
+    DEFAULT_BASE_URL, NewsItem, StationCandidate, TrainCandidate, ViaggiatrenoClient,
+};
+pub use error::{Error, Result};
+pub use events::TrainEvent;
+pub use models::{
+    RunType, StationBoardEntry, Stop, StopKind, TrainStatus, TravelLeg, TravelSolution,
+    TravelSolutions,
+};
@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use colored::Colorize;
+use tabular::{Table, row};
+use viaggiatreno::{Result, StationBoardEntry, StationCandidate, TrainStatus, circulation, time};
+
+use crate::context::Context;
+use crate::output::{self, LineTrainReport};
+use crate::plot::{self, Charset};
+use crate::station;
+use crate::track_train;
+use crate::watch;
+
+/// A train running between the two stations of a line.
+pub struct LineTrain {
+    pub train: TrainStatus,
+    /// Whether the train runs from the first station to the second one
+    pub forward: bool,
+}
+
+impl LineTrain {
+    /// Stations in the order the train runs through them (e.g. "ROMA TERMINI → FIRENZE S. M. NOVELLA").
+    pub fn direction(&self, from: &StationCandidate, to: &StationCandidate) -> String {
+        if self.forward {
+            format!("{} → {}", from.name, to.name)
+        } else {
+            format!("{} → {}", to.name, from.name)
+        }
+    }
+}
+
+/// How the trains of a line are shown.
+pub enum LineView {
+    Table,
+    Plot(&'static Charset),
+}
+
+pub async fn line(
+    ctx: &Context,
+    from: &str,
+    to: &str,
+    filter: Option<&str>,
+    view: LineView,
+    watch: Option<Duration>,
+) -> Result<()> {
+    let from_station = station::find_station(ctx, from).await?;
+    let to_station = station::find_station(ctx, to).await?;
+
+    match watch {
+        Some(interval) => {
+            watch::run(interval, async || {
+                print_line_info(ctx, &from_station, &to_station, filter, &view, watch).await
+            })
+            .await
+        }
+        None => print_line_info(ctx, &from_station, &to_station, filter, &view, None).await,
+    }
+}
+
+async fn print_line_info(
+    ctx: &Context,
+    from: &StationCandidate,
+    to: &StationCandidate,
+    filter: Option<&str>,
+    view: &LineView,
+    watch: Option<Duration>,
+) -> Result<()> {
+    let trains = trains_between(ctx, from, to, filter).await?;
+
+    if !ctx.output.is_human() {
+        let reports: Vec<_> = trains
+            .iter()
+            .map(|t| LineTrainReport::new(&t.train, &t.direction(from, to)))
+            .collect();
+        return output::print_records(ctx.output, "line_trains", "line_train", &reports);
+    }
+
+    if let Some(interval) = watch {
+        watch::clear_screen(interval);
+    }
+
+    println!(
+        "Trains currently running between {} and {}:\n",
+        from.name.cyan(),
+        to.name.cyan()
+    );
+
+    if let LineView::Plot(charset) = view {
+        print!(
+            "{}",
+            plot::render(
+                (&from.code, &from.name),
+                (&to.code, &to.name),
+                &trains,
+                time::now(),
+                charset
+            )
+        );
+        return Ok(());
+    }
+
+    if trains.is_empty() {
+        println!("No train found.");
+        return Ok(());
+    }
+
+    let mut table = Table::new("{:<}  {:<}  {:<} {:<}  {:>}  {:<}");
+    table.add_row(row!(
+        "Train".bold(),
+        "Direction".bold(),
+        "Last detection".bold(),
+        "",
+        "Delay".bold(),
+        "Next stop".bold()
+    ));
+    for line_train in &trains {
+        let report = LineTrainReport::new(&line_train.train, &line_train.direction(from, to));
+        table.add_row(row!(
+            report.label.as_deref().unwrap_or("--").bold(),
+            &report.direction,
+            report.last_detection_station.as_deref().unwrap_or("--"),
+            track_train::format_time(report.last_detection_time),
+            report.delay.map_or("--".to_string(), station::format_delay),
+            report.next_stop.as_deref().unwrap_or("--")
+        ));
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+/// Finds the trains currently running between two stations, stopping at both of them.
+/// Trains are gathered from the arrival and departure boards of both stations, then from the
+/// boards of the stations in between, where the trains too far from both ends are listed.
+pub async fn trains_between(
+    ctx: &Context,
+    from: &StationCandidate,
+    to: &StationCandidate,
+    filter: Option<&str>,
+) -> Result<Vec<LineTrain>> {
+    let now = time::now();
+    let filter = |entry: &StationBoardEntry| filter.is_none_or(|c| entry.has_category(c));
+
+    let mut trains = circulation::circulating_trains(
+        &ctx.client,
+        &[from.code.as_str(), to.code.as_str()],
+        now,
+        filter,
+    )
+    .await?;
+
+    let intermediate = intermediate_stations(&trains, from, to);
+    if !intermediate.is_empty() {
+        let codes: Vec<_> = intermediate.iter().map(String::as_str).collect();
+        // The trains of the ends are already known, a failure here only hides the others
+        if let Ok(more) =
+            circulation::circulating_trains_except(&ctx.client, &codes, now, filter, &trains).await
+        {
+            trains.extend(more);
+        }
+    }
+
+    let mut result = Vec::new();
+    for train in trains {
+        let (Some(from_index), Some(to_index)) =
+            (train.stop_index(&from.code), train.stop_index(&to.code))
+        else {
+            continue;
+        };
+        let Some(last_reached) = train.last_reached_stop_index() else {
+            continue;
+        };
+
+        // The train must have left the first of the two stations on its route, without having
+        // reached the second one yet
+        let (first, second) = (from_index.min(to_index), from_index.max(to_index));
+        if last_reached < first || last_reached >= second {
+            continue;
+        }
+
+        result.push(LineTrain {
+            train,
+            forward: from_index < to_index,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Codes of the stations where the given trains stop between the two stations, in order of
+/// appearance.
+fn intermediate_stations(
+    trains: &[TrainStatus],
+    from: &StationCandidate,
+    to: &StationCandidate,
+) -> Vec<String> {
+    let mut codes: Vec<String> = Vec::new();
+
+    for train in trains {
+        let (Some(from_index), Some(to_index)) =
+            (train.stop_index(&from.code), train.stop_index(&to.code))
+        else {
+            continue;
+        };
+        let (first, second) = (from_index.min(to_index), from_index.max(to_index));
+
+        for stop in &train.stops[first + 1..second] {
+            if !codes.contains(&stop.station_code) {
+                codes.push(stop.station_code.clone());
+            }
+        }
+    }
+
+    codes
+}
@@ -0,0 +1,92 @@
+use std::cmp::Reverse;
+
+use clap::ValueEnum;
+use colored::Colorize;
+use tabular::{Table, row};
+use viaggiatreno::{Result, circulation, time};
+
+use crate::context::Context;
+use crate::output::{self, RunningTrainReport};
+use crate::station;
+use crate::track_train;
+
+/// Stations served by most long distance trains: every running train is looked up on their boards.
+const HUB_STATIONS: &[&str] = &[
+    "S01700", // MILANO CENTRALE
+    "S00219", // TORINO PORTA NUOVA
+    "S02593", // VENEZIA SANTA LUCIA
+    "S05043", // BOLOGNA CENTRALE
+    "S06421", // FIRENZE S. M. NOVELLA
+    "S08409", // ROMA TERMINI
+    "S09218", // NAPOLI CENTRALE
+    "S11119", // BARI CENTRALE
+    "S11781", // REGGIO DI CALABRIA CENTRALE
+];
+
+/// Categories of long distance trains: Frecciarossa, Frecciargento, Frecciabianca, Intercity,
+/// Intercity Notte and Eurocity.
+const LONG_DISTANCE_CATEGORIES: &[&str] = &["FR", "FA", "FB", "IC", "ICN", "EC"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// Sort by train category and number
+    #[default]
+    Train,
+    /// Sort by delay, most delayed trains first
+    Delay,
+}
+
+pub async fn long_distance(ctx: &Context, sort: SortOrder) -> Result<()> {
+    let now = time::now();
+
+    let mut trains = circulation::circulating_trains(&ctx.client, HUB_STATIONS, now, |entry| {
+        LONG_DISTANCE_CATEGORIES
+            .iter()
+            .any(|category| entry.has_category(category))
+    })
+    .await?;
+
+    match sort {
+        SortOrder::Train => trains.sort_by(|a, b| {
+            (a.category.as_deref(), a.number).cmp(&(b.category.as_deref(), b.number))
+        }),
+        // Trains without a known delay go last
+        SortOrder::Delay => trains.sort_by_key(|t| Reverse(t.delay.map_or(i64::MIN, |d| d))),
+    }
+
+    let reports: Vec<_> = trains.iter().map(RunningTrainReport::from).collect();
+
+    if !ctx.output.is_human() {
+        return output::print_records(ctx.output, "running_trains", "running_train", &reports);
+    }
+
+    println!("{}\n", "Long distance trains currently running:".cyan());
+
+    if reports.is_empty() {
+        println!("No train found.");
+        return Ok(());
+    }
+
+    let mut table = Table::new("{:<}  {:<}  {:<}  {:<} {:<}  {:>}");
+    table.add_row(row!(
+        "Train".bold(),
+        "Origin".bold(),
+        "Destination".bold(),
+        "Last detection".bold(),
+        "",
+        "Delay".bold()
+    ));
+    for report in &reports {
+        table.add_row(row!(
+            report.label.as_deref().unwrap_or("--").bold(),
+            report.origin.as_deref().unwrap_or("--"),
+            report.destination.as_deref().unwrap_or("--"),
+            report.last_detection_station.as_deref().unwrap_or("--"),
+            track_train::format_time(report.last_detection_time),
+            report.delay.map_or("--".to_string(), station::format_delay)
+        ));
+    }
+    println!("{table}");
+
+    Ok(())
+}
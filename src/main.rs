@@ -1,14 +1,54 @@
+use std::io::IsTerminal;
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveDateTime};
 use clap::{Parser, Subcommand};
+use viaggiatreno::ViaggiatrenoClient;
+use viaggiatreno::events::DiffOptions;
+
+use crate::cli_input::Pick;
+use crate::context::Context;
+use crate::history::HistoryStore;
+use crate::output::OutputFormat;
+use crate::track_train::TrainId;
 
+mod branch;
 mod cli_input;
+mod connection;
+mod context;
+mod history;
+mod hooks;
+mod line;
+mod long_distance;
 mod news;
+mod output;
+mod plan;
+mod plot;
 mod station;
 mod track_train;
+mod watch;
 
 #[derive(Parser)]
 #[command(version, about, long_about=None)]
 #[command(next_line_help = true)]
 struct Cli {
+    /// base URL of the Viaggiatreno API, useful to run against a proxy or a mock server
+    #[clap(long, global = true, env = "INFOTRENO_BASE_URL")]
+    #[arg(default_value = viaggiatreno::DEFAULT_BASE_URL)]
+    base_url: String,
+    /// output format: colored text, or machine-readable JSON with a versioned schema
+    #[clap(short, long, global = true, value_enum)]
+    #[arg(default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+    /// never prompt for input: fail with an "ambiguous" error when several results match.
+    /// Enabled automatically when the standard input is not a terminal
+    #[clap(long, global = true)]
+    #[arg(default_value_t = false)]
+    non_interactive: bool,
+    /// do not record the delays of the trains shown in the local delay history
+    #[clap(long, global = true)]
+    #[arg(default_value_t = false)]
+    no_history: bool,
     #[clap(subcommand)]
     command: Commands,
 }
@@ -19,19 +59,56 @@ enum Commands {
     /// Note: if a certain train code corresponds to multiple trains, you will be asked to choose one
     #[clap(visible_alias = "t")]
     Track {
-        /// train code
-        code: u32,
+        /// train code, optionally with its category (e.g. "9612" or "FR 9612"); several trains
+        /// can be tracked at once by giving more codes
+        #[clap(required = true)]
+        codes: Vec<String>,
         /// index of the train to track, useful when the code corresponds to multiple trains
         #[clap(short, long)]
         index: Option<usize>,
+        /// departure date of the train from its origin (YYYY-MM-DD), e.g. yesterday's run
+        #[clap(long)]
+        date: Option<NaiveDate>,
+        /// origin station of the train, by name or code, when the code is used by several trains
+        #[clap(long)]
+        origin: Option<String>,
         /// print all the train stops (verbose)
         #[clap(short, long)]
         #[arg(default_value_t = false)]
         stops: bool,
-        /// watch mode: refresh tracking data every minute
+        /// when tracking several trains, print only the stops of the given train codes
+        /// (e.g. "--stops-of 9612,2143")
+        #[clap(long, value_delimiter = ',', conflicts_with = "stops")]
+        stops_of: Vec<u32>,
+        /// watch mode: refresh tracking data every minute, or every --interval seconds
         #[clap(short, long)]
         #[arg(default_value_t = false)]
         watch: bool,
+        /// seconds between two refreshes in watch mode
+        #[clap(long, requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: Option<u64>,
+        /// minimum delay change, in minutes, shown in the event log of watch mode
+        #[clap(long, requires = "watch", default_value_t = 5)]
+        delay_threshold: i64,
+        /// delays, in minutes, reported as an event in watch mode when reached (e.g. "5,15,30")
+        #[clap(long, requires = "watch", value_delimiter = ',')]
+        delay_alert: Vec<i64>,
+        /// ring the terminal bell when something changes in watch mode
+        #[clap(long, requires = "watch")]
+        bell: bool,
+        /// shell command run for each event in watch mode, receiving the event as JSON on its
+        /// standard input and as INFOTRENO_* environment variables
+        #[clap(long, requires = "watch")]
+        on_event: Option<String>,
+        /// URL receiving each event of watch mode as a JSON POST request
+        #[clap(long, requires = "watch")]
+        webhook: Option<String>,
+        /// timeout, in seconds, of each webhook request
+        #[clap(long, requires = "webhook", default_value_t = 10)]
+        webhook_timeout: u64,
+        /// number of times a failed webhook request is retried
+        #[clap(long, requires = "webhook", default_value_t = 3)]
+        webhook_retries: u32,
     },
     /// find arrival and departure times of trains at a certain station.
     /// It is possible to search for a station by the beginning of its name; a prompt will ask to choose the desired station
@@ -51,6 +128,108 @@ enum Commands {
         /// filter results by train type code (e.g. "FR", "IC", "REG")
         #[clap(short, long)]
         filter: Option<String>,
+        /// station to choose when the name corresponds to multiple stations: "first", or its
+        /// number in the list of stations
+        #[clap(short, long)]
+        pick: Option<Pick>,
+        /// watch mode: redraw the boards every minute, or every --interval seconds, highlighting
+        /// the trains whose delay or platform changed
+        #[clap(short, long)]
+        watch: bool,
+        /// seconds between two refreshes in watch mode
+        #[clap(long, requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: Option<u64>,
+    },
+    /// find travel solutions between two stations, with the trains to take and their changes.
+    /// Stations can be searched by name or code, as in the station command
+    #[clap(visible_alias = "p")]
+    Plan {
+        /// departure station name or code
+        from: String,
+        /// arrival station name or code
+        to: String,
+        /// departure date and time, as "YYYY-MM-DD HH:MM" or "HH:MM" for today (default: now)
+        #[clap(short, long, value_parser = plan::parse_datetime)]
+        at: Option<NaiveDateTime>,
+    },
+    /// print the delay history of a train at a certain station, day by day, with summary statistics.
+    /// History is recorded locally whenever the train is shown by the track or station commands
+    #[clap(visible_alias = "h")]
+    History {
+        /// train code
+        code: u32,
+        /// station name or station code (e.g. "Milano Centrale" or "S01700")
+        #[clap(short, long)]
+        station: String,
+    },
+    /// list the trains currently running between two stations of a line, with their direction,
+    /// last detected position and delay
+    #[clap(visible_alias = "l")]
+    Line {
+        /// first station name or code
+        from: String,
+        /// second station name or code
+        to: String,
+        /// filter results by train type code (e.g. "FR", "IC", "REG")
+        #[clap(short, long)]
+        filter: Option<String>,
+        /// draw the line with the trains at their estimated position, instead of a table
+        #[clap(short, long)]
+        plot: bool,
+        /// draw the line using only ASCII characters
+        #[clap(long, requires = "plot")]
+        ascii: bool,
+        /// refresh the trains every minute, or every --interval seconds
+        #[clap(short, long)]
+        watch: bool,
+        /// seconds between two refreshes in watch mode
+        #[clap(long, requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: Option<u64>,
+    },
+    /// list the regional trains currently running on a branch line, with their next stop and
+    /// estimated arrival
+    #[clap(visible_alias = "b")]
+    Branch {
+        /// name of a bundled or user defined branch (see --list)
+        #[clap(required_unless_present_any = ["stations", "list"])]
+        name: Option<String>,
+        /// ordered, comma separated list of station names or codes, instead of a named branch
+        #[clap(short = 'S', long, value_delimiter = ',', conflicts_with = "name")]
+        stations: Vec<String>,
+        /// filter results by train type code, instead of showing regional trains (e.g. "REG")
+        #[clap(short, long)]
+        filter: Option<String>,
+        /// list the available branches
+        #[clap(short, long, conflicts_with_all = ["name", "stations"])]
+        list: bool,
+    },
+    /// list all the long distance trains (Frecce, Intercity, Eurocity) currently running
+    #[clap(visible_alias = "ld")]
+    LongDistance {
+        /// order of the listed trains
+        #[clap(short, long, value_enum, default_value_t)]
+        sort: long_distance::SortOrder,
+    },
+    /// check whether a change between two trains can be made, from their current delays
+    #[clap(visible_alias = "c")]
+    Connection {
+        /// code of the train to get off, optionally with its category (e.g. "9612" or "FR 9612")
+        arriving: TrainId,
+        /// code of the train to get on, optionally with its category
+        departing: TrainId,
+        /// station of the change, name or code; by default, the first stop of the arriving train
+        /// where the departing train stops too
+        #[clap(short, long)]
+        at: Option<String>,
+        /// minimum time, in minutes, needed to change train
+        #[clap(short, long, default_value_t = 5)]
+        min_transfer: i64,
+        /// refresh the estimate every minute, or every --interval seconds
+        #[clap(short, long)]
+        watch: bool,
+        /// seconds between two refreshes in watch mode
+        #[clap(long, requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+        interval: Option<u64>,
     },
     /// get information about line disruptions from Trenitalia
     #[clap(visible_alias = "n")]
@@ -66,28 +245,160 @@ enum Commands {
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    let ctx = Context {
+        client: ViaggiatrenoClient::with_base_url(&cli.base_url),
+        output: cli.output,
+        interactive: !cli.non_interactive && std::io::stdin().is_terminal(),
+        history: if cli.no_history {
+            None
+        } else {
+            HistoryStore::open_default()
+        },
+    };
 
     let command_result = match cli.command {
         Commands::Track {
-            code,
+            codes,
             index,
+            date,
+            origin,
             stops,
+            stops_of,
             watch,
-        } => track_train::track(code, index, stops, watch).await,
+            interval,
+            delay_threshold,
+            delay_alert,
+            bell,
+            on_event,
+            webhook,
+            webhook_timeout,
+            webhook_retries,
+        } => {
+            async {
+                let codes = track_train::TrainId::parse_all(&codes)?;
+                let webhook = webhook
+                    .map(|url| {
+                        hooks::Webhook::new(
+                            &url,
+                            Duration::from_secs(webhook_timeout),
+                            webhook_retries,
+                        )
+                    })
+                    .transpose()?;
+                let watch =
+                    watch_interval(watch, interval).map(|interval| track_train::WatchOptions {
+                        interval,
+                        diff: DiffOptions {
+                            delay_change: delay_threshold,
+                            delay_thresholds: delay_alert,
+                        },
+                        bell,
+                        on_event,
+                        webhook,
+                    });
+                let selection = track_train::TrainSelection {
+                    index,
+                    date,
+                    origin,
+                };
+                let stops = if stops {
+                    Some(&[][..])
+                } else if !stops_of.is_empty() {
+                    Some(stops_of.as_slice())
+                } else {
+                    None
+                };
+                track_train::track(&ctx, &codes, &selection, stops, watch).await
+            }
+            .await
+        }
         Commands::Station {
             station,
             arrivals,
             departures,
             filter,
-        } => station::station(&station, arrivals, departures, filter.as_deref()).await,
-        Commands::News { verbose } => news::print_news(verbose).await,
+            pick,
+            watch,
+            interval,
+        } => {
+            station::station(
+                &ctx,
+                &station,
+                arrivals,
+                departures,
+                filter.as_deref(),
+                pick,
+                watch_interval(watch, interval),
+            )
+            .await
+        }
+        Commands::Plan { from, to, at } => plan::plan(&ctx, &from, &to, at).await,
+        Commands::History { code, station } => history::history(&ctx, code, &station),
+        Commands::Line {
+            from,
+            to,
+            filter,
+            plot,
+            ascii,
+            watch,
+            interval,
+        } => {
+            let view = match (plot, ascii) {
+                (false, _) => line::LineView::Table,
+                (true, false) => line::LineView::Plot(&plot::Charset::UNICODE),
+                (true, true) => line::LineView::Plot(&plot::Charset::ASCII),
+            };
+            line::line(
+                &ctx,
+                &from,
+                &to,
+                filter.as_deref(),
+                view,
+                watch_interval(watch, interval),
+            )
+            .await
+        }
+        Commands::Branch {
+            name,
+            stations,
+            filter,
+            list,
+        } => {
+            if list {
+                branch::list_branches(&ctx)
+            } else {
+                branch::branch(&ctx, name.as_deref(), &stations, filter.as_deref()).await
+            }
+        }
+        Commands::Connection {
+            arriving,
+            departing,
+            at,
+            min_transfer,
+            watch,
+            interval,
+        } => {
+            connection::connection(
+                &ctx,
+                &arriving,
+                &departing,
+                at.as_deref(),
+                min_transfer,
+                watch_interval(watch, interval),
+            )
+            .await
+        }
+        Commands::LongDistance { sort } => long_distance::long_distance(&ctx, sort).await,
+        Commands::News { verbose } => news::print_news(&ctx, verbose).await,
     };
 
     if let Err(e) = command_result {
-        if e.is_request() {
-            eprintln!("Cannot complete request.");
-        } else {
-            eprintln!("Error: {}", e);
-        }
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
     }
 }
+
+/// Time between two refreshes, if watch mode is enabled.
+fn watch_interval(watch: bool, interval: Option<u64>) -> Option<Duration> {
+    watch.then(|| interval.map_or(watch::DEFAULT_INTERVAL, Duration::from_secs))
+}
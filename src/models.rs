@@ -0,0 +1,384 @@
+use chrono::{DateTime, NaiveDateTime};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::time::milliseconds_option;
+
+/// Status of a train, as returned by the `andamentoTreno` endpoint.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TrainStatus {
+    #[serde(rename = "numeroTreno")]
+    pub number: Option<u32>,
+    /// Train label including its category (e.g. "FR 9612")
+    #[serde(rename = "compNumeroTreno")]
+    pub label: Option<String>,
+    /// Train category code (e.g. "FR", "REG")
+    #[serde(rename = "categoria")]
+    pub category: Option<String>,
+    #[serde(rename = "idOrigine")]
+    pub origin_id: Option<String>,
+    #[serde(rename = "origine")]
+    pub origin: Option<String>,
+    #[serde(rename = "destinazione")]
+    pub destination: Option<String>,
+    /// Origin of international trains starting outside of Italy
+    #[serde(rename = "origineEstera")]
+    pub international_origin: Option<String>,
+    /// Destination of international trains ending outside of Italy
+    #[serde(rename = "destinazioneEstera")]
+    pub international_destination: Option<String>,
+    /// Departure time from the international origin
+    #[serde(rename = "oraPartenzaEstera", with = "milliseconds_option")]
+    pub international_departure_time: Option<DateTime<Tz>>,
+    /// Scheduled departure time from the origin, formatted as "HH:MM"
+    #[serde(rename = "compOrarioPartenza")]
+    pub departure_time: Option<String>,
+    /// Provision code: 1 means that the train is canceled
+    #[serde(rename = "provvedimento")]
+    pub provision: Option<u8>,
+    /// Whether the train runs as scheduled, or is canceled, limited or rerouted
+    #[serde(rename = "tipoTreno")]
+    pub run_type: Option<RunType>,
+    /// Message about the provisions affecting the run (e.g. "Treno limitato a ROMA TERMINI")
+    #[serde(rename = "subTitle")]
+    pub subtitle: Option<String>,
+    /// Messages about the provisions affecting the run
+    #[serde(rename = "compProvvedimenti")]
+    pub provision_messages: Option<Vec<String>>,
+    #[serde(rename = "nonPartito")]
+    pub not_departed: Option<bool>,
+    /// Delay in minutes
+    #[serde(rename = "ritardo")]
+    pub delay: Option<i64>,
+    #[serde(rename = "stazioneUltimoRilevamento")]
+    pub last_detection_station: Option<String>,
+    #[serde(rename = "oraUltimoRilevamento", with = "milliseconds_option")]
+    pub last_detection_time: Option<DateTime<Tz>>,
+    /// Departure date of the train from its origin
+    #[serde(rename = "dataPartenzaTreno", with = "milliseconds_option")]
+    pub departure_date: Option<DateTime<Tz>>,
+    #[serde(rename = "fermate")]
+    pub stops: Vec<Stop>,
+}
+
+impl TrainStatus {
+    pub fn is_canceled(&self) -> bool {
+        self.provision == Some(1) || self.run_type == Some(RunType::Canceled)
+    }
+
+    /// Whether part of the run is canceled, or the train follows a different route.
+    pub fn is_altered(&self) -> bool {
+        matches!(
+            self.run_type,
+            Some(
+                RunType::PartiallyCanceled
+                    | RunType::OriginMoved
+                    | RunType::Limited
+                    | RunType::Rerouted
+            )
+        )
+    }
+
+    /// Messages about the provisions affecting the run, without duplicates.
+    pub fn provisions(&self) -> Vec<&str> {
+        let mut provisions: Vec<&str> = Vec::new();
+        let messages = self
+            .subtitle
+            .iter()
+            .chain(self.provision_messages.iter().flatten());
+        for message in messages.map(|m| m.trim()).filter(|m| !m.is_empty()) {
+            if !provisions.contains(&message) {
+                provisions.push(message);
+            }
+        }
+        provisions
+    }
+
+    pub fn is_not_departed(&self) -> bool {
+        self.not_departed.unwrap_or_default()
+    }
+
+    /// Whether the train has reached its final stop, skipping the canceled ones.
+    pub fn is_arrived(&self) -> bool {
+        self.stops
+            .iter()
+            .rfind(|stop| stop.kind != StopKind::Canceled)
+            .is_some_and(|stop| matches!(stop.kind, StopKind::Regular | StopKind::Extraordinary))
+    }
+
+    /// First stop the train has not reached yet.
+    pub fn next_stop(&self) -> Option<&Stop> {
+        self.stops
+            .iter()
+            .find(|stop| stop.kind == StopKind::Pending)
+    }
+
+    /// Index of the last stop the train has reached.
+    pub fn last_reached_stop_index(&self) -> Option<usize> {
+        self.stops
+            .iter()
+            .rposition(|stop| matches!(stop.kind, StopKind::Regular | StopKind::Extraordinary))
+    }
+
+    /// Index of the stop at the given station.
+    pub fn stop_index(&self, station_code: &str) -> Option<usize> {
+        self.stops
+            .iter()
+            .position(|stop| stop.station_code == station_code)
+    }
+}
+
+/// A stop of a train.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Stop {
+    /// Station code (e.g. "S01700")
+    #[serde(rename = "id")]
+    pub station_code: String,
+    #[serde(rename = "stazione")]
+    pub station: String,
+    #[serde(rename = "arrivo_teorico", with = "milliseconds_option")]
+    pub scheduled_arrival: Option<DateTime<Tz>>,
+    #[serde(rename = "partenza_teorica", with = "milliseconds_option")]
+    pub scheduled_departure: Option<DateTime<Tz>>,
+    #[serde(rename = "arrivoReale", with = "milliseconds_option")]
+    pub actual_arrival: Option<DateTime<Tz>>,
+    #[serde(rename = "partenzaReale", with = "milliseconds_option")]
+    pub actual_departure: Option<DateTime<Tz>>,
+    /// Delay in minutes
+    #[serde(rename = "ritardo")]
+    pub delay: Option<i64>,
+    #[serde(rename = "binarioProgrammatoArrivoDescrizione")]
+    pub scheduled_arrival_platform: Option<String>,
+    #[serde(rename = "binarioEffettivoArrivoDescrizione")]
+    pub actual_arrival_platform: Option<String>,
+    #[serde(rename = "binarioProgrammatoPartenzaDescrizione")]
+    pub scheduled_departure_platform: Option<String>,
+    #[serde(rename = "binarioEffettivoPartenzaDescrizione")]
+    pub actual_departure_platform: Option<String>,
+    #[serde(rename = "actualFermataType")]
+    pub kind: StopKind,
+}
+
+impl Stop {
+    /// Scheduled platform, preferring the arrival one.
+    pub fn scheduled_platform(&self) -> Option<&str> {
+        non_empty(&self.scheduled_arrival_platform)
+            .or_else(|| non_empty(&self.scheduled_departure_platform))
+    }
+
+    /// Actual platform, preferring the arrival one.
+    pub fn actual_platform(&self) -> Option<&str> {
+        non_empty(&self.actual_arrival_platform)
+            .or_else(|| non_empty(&self.actual_departure_platform))
+    }
+}
+
+/// Kind of a train stop, from the `actualFermataType` field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "u8")]
+pub enum StopKind {
+    /// The train has not reached the stop yet
+    #[default]
+    Pending,
+    /// The train has stopped as scheduled
+    Regular,
+    /// The train has made an extraordinary, unscheduled stop
+    Extraordinary,
+    /// The stop has been canceled
+    Canceled,
+    /// A value not known to this crate
+    Unknown(u8),
+}
+
+impl StopKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Regular => "regular",
+            Self::Extraordinary => "extraordinary",
+            Self::Canceled => "canceled",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl From<u8> for StopKind {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Pending,
+            1 => Self::Regular,
+            2 => Self::Extraordinary,
+            3 => Self::Canceled,
+            n => Self::Unknown(n),
+        }
+    }
+}
+
+/// Kind of run of a train, from the `tipoTreno` field.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum RunType {
+    /// The train runs as scheduled ("PG")
+    Regular,
+    /// The whole train is canceled ("ST")
+    Canceled,
+    /// Some stops in the middle of the run are canceled ("PP")
+    PartiallyCanceled,
+    /// The train departs from a station after its scheduled origin ("SI")
+    OriginMoved,
+    /// The train ends its run before its scheduled destination ("SF")
+    Limited,
+    /// The train follows a different route ("DV")
+    Rerouted,
+    /// A value not known to this crate
+    Unknown(String),
+}
+
+impl RunType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Regular => "regular",
+            Self::Canceled => "canceled",
+            Self::PartiallyCanceled => "partially_canceled",
+            Self::OriginMoved => "origin_moved",
+            Self::Limited => "limited",
+            Self::Rerouted => "rerouted",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl From<String> for RunType {
+    fn from(value: String) -> Self {
+        match value.trim() {
+            "PG" => Self::Regular,
+            "ST" => Self::Canceled,
+            "PP" => Self::PartiallyCanceled,
+            "SI" => Self::OriginMoved,
+            "SF" => Self::Limited,
+            "DV" => Self::Rerouted,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+/// A train listed on the arrivals or departures board of a station, as returned by the `arrivi`
+/// and `partenze` endpoints.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StationBoardEntry {
+    #[serde(rename = "numeroTreno")]
+    pub number: Option<u32>,
+    /// Train label including its category (e.g. "FR 9612")
+    #[serde(rename = "compNumeroTreno")]
+    pub label: String,
+    /// Train category description (e.g. "FR", "REG")
+    #[serde(rename = "categoriaDescrizione")]
+    pub category: Option<String>,
+    /// Origin station, only set on arrival boards
+    #[serde(rename = "origine")]
+    pub origin: Option<String>,
+    /// Destination station, only set on departure boards
+    #[serde(rename = "destinazione")]
+    pub destination: Option<String>,
+    #[serde(rename = "codOrigine")]
+    pub origin_id: Option<String>,
+    /// Departure date of the train from its origin
+    #[serde(rename = "dataPartenzaTreno", with = "milliseconds_option")]
+    pub departure_date: Option<DateTime<Tz>>,
+    #[serde(rename = "orarioArrivo", with = "milliseconds_option")]
+    pub scheduled_arrival: Option<DateTime<Tz>>,
+    #[serde(rename = "orarioPartenza", with = "milliseconds_option")]
+    pub scheduled_departure: Option<DateTime<Tz>>,
+    /// Scheduled arrival time, formatted as "HH:MM"
+    #[serde(rename = "compOrarioArrivo")]
+    pub arrival_time: Option<String>,
+    /// Scheduled departure time, formatted as "HH:MM"
+    #[serde(rename = "compOrarioPartenza")]
+    pub departure_time: Option<String>,
+    /// Delay in minutes
+    #[serde(rename = "ritardo")]
+    pub delay: Option<i64>,
+    #[serde(rename = "binarioProgrammatoArrivoDescrizione")]
+    pub scheduled_arrival_platform: Option<String>,
+    #[serde(rename = "binarioEffettivoArrivoDescrizione")]
+    pub actual_arrival_platform: Option<String>,
+    #[serde(rename = "binarioProgrammatoPartenzaDescrizione")]
+    pub scheduled_departure_platform: Option<String>,
+    #[serde(rename = "binarioEffettivoPartenzaDescrizione")]
+    pub actual_departure_platform: Option<String>,
+}
+
+impl StationBoardEntry {
+    /// Whether the train category matches the given one (e.g. "FR", "REG"), ignoring case.
+    pub fn has_category(&self, category: &str) -> bool {
+        self.category
+            .as_deref()
+            .is_some_and(|c| c.trim().eq_ignore_ascii_case(category.trim()))
+    }
+}
+
+/// Travel solutions between two stations, as returned by the `soluzioniViaggioNew` endpoint.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TravelSolutions {
+    #[serde(rename = "origine")]
+    pub origin: Option<String>,
+    #[serde(rename = "destinazione")]
+    pub destination: Option<String>,
+    #[serde(rename = "soluzioni")]
+    pub solutions: Vec<TravelSolution>,
+}
+
+/// A travel solution, made of one leg for each train to take.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TravelSolution {
+    /// Travel duration, formatted as "HH:MM"
+    #[serde(rename = "durata")]
+    pub duration: Option<String>,
+    #[serde(rename = "vehicles")]
+    pub legs: Vec<TravelLeg>,
+}
+
+impl TravelSolution {
+    pub fn departure(&self) -> Option<NaiveDateTime> {
+        self.legs.first().and_then(|leg| leg.departure)
+    }
+
+    pub fn arrival(&self) -> Option<NaiveDateTime> {
+        self.legs.last().and_then(|leg| leg.arrival)
+    }
+
+    /// Number of train changes.
+    pub fn changes(&self) -> usize {
+        self.legs.len().saturating_sub(1)
+    }
+}
+
+/// A leg of a travel solution, traveled on a single train.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TravelLeg {
+    #[serde(rename = "origine")]
+    pub origin: Option<String>,
+    #[serde(rename = "destinazione")]
+    pub destination: Option<String>,
+    /// Departure time, in the Europe/Rome timezone
+    #[serde(rename = "orarioPartenza")]
+    pub departure: Option<NaiveDateTime>,
+    /// Arrival time, in the Europe/Rome timezone
+    #[serde(rename = "orarioArrivo")]
+    pub arrival: Option<NaiveDateTime>,
+    /// Train category description (e.g. "Frecciarossa", "Regionale")
+    #[serde(rename = "categoriaDescrizione")]
+    pub category: Option<String>,
+    #[serde(rename = "numeroTreno")]
+    pub number: String,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
@@ -1,85 +1,45 @@
 use colored::Colorize;
-use scraper::{Html, Selector};
+use viaggiatreno::Result;
 
 use crate::cli_input;
+use crate::context::Context;
+use crate::output::{self, NewsReport};
 
-pub async fn print_news(is_verbose: bool) -> Result<(), reqwest::Error> {
-    let url =
-        "http://www.viaggiatreno.it/infomobilitamobile/resteasy/viaggiatreno/infomobilitaRSS/false";
+pub async fn print_news(ctx: &Context, is_verbose: bool) -> Result<()> {
+    let news = ctx.client.news().await?;
 
-    let res = reqwest::get(url).await?.text().await?;
-
-    let fragment = Html::parse_fragment(&res);
-    let selector = Selector::parse("li").unwrap();
-
-    let news = fragment.select(&selector).collect::<Vec<_>>();
+    if !ctx.output.is_human() {
+        let reports: Vec<_> = news.iter().map(NewsReport::from).collect();
+        return output::print_records(ctx.output, "news", "news_item", &reports);
+    }
 
     if news.is_empty() {
         println!("No news available.");
         return Ok(());
     }
 
-    for (i, element) in news.iter().enumerate() {
-        let mut children_iter = element.child_elements();
-
-        let Some(title_element) = children_iter.next() else {
-            break;
-        };
-        let title = title_element.inner_html();
-        let is_highlighted = title_element
-            .value()
-            .attr("class")
-            .unwrap()
-            .contains("inEvidenza");
-
-        let title = if is_highlighted {
-            title.bright_red()
+    for (i, item) in news.iter().enumerate() {
+        let title = if item.is_highlighted {
+            item.title.bright_red()
         } else {
-            title.normal()
+            item.title.normal()
         };
         println!("{}. {}\n", i + 1, title.bold());
 
         if is_verbose {
-            let info_text = children_iter
-                .next()
-                .unwrap()
-                .text()
-                .collect::<String>()
-                .trim()
-                .replace('\t', "");
-            println!("{}\n", info_text);
+            println!("{}\n", item.body);
         }
     }
 
-    if is_verbose {
+    if is_verbose || !ctx.interactive {
         return Ok(());
     }
 
-    println!("{}", "Select a news header to expand:".dimmed());
-
-    loop {
-        let index = cli_input::get_index();
+    let titles: Vec<_> = news.iter().map(|item| item.title.clone()).collect();
 
-        if index == 0 {
-            return Ok(());
-        }
-
-        if index > news.len() {
-            println!("Invalid index.");
-            continue;
-        }
-
-        let info_text = news
-            .get(index - 1)
-            .unwrap()
-            .child_elements()
-            .nth(1)
-            .unwrap()
-            .text()
-            .collect::<String>()
-            .trim()
-            .replace('\t', "");
-
-        println!("{}\n", info_text);
+    while let Some(index) = cli_input::prompt_selection("Select a news item to expand:", &titles)? {
+        println!("\n{}\n", news[index].body);
     }
+
+    Ok(())
 }
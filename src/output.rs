@@ -0,0 +1,633 @@
+use chrono::{DateTime, NaiveDateTime, TimeDelta, TimeZone};
+use chrono_tz::{Europe::Rome, Tz};
+use clap::ValueEnum;
+use serde::Serialize;
+use viaggiatreno::{
+    Error, NewsItem, Result, RunType, StationBoardEntry, Stop, StopKind, TrainEvent, TrainStatus,
+    TravelLeg, TravelSolution,
+};
+
+/// Version of the machine-readable output schema.
+/// It is increased whenever a field is removed or changes meaning; new fields may be added without
+/// increasing it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// colored text for terminals
+    #[default]
+    Human,
+    /// a single JSON document
+    Json,
+    /// one JSON document per line, one for each record
+    Ndjson,
+    /// comma-separated values, with a header row
+    Csv,
+    /// tab-separated values, with a header row
+    Tsv,
+}
+
+impl OutputFormat {
+    pub fn is_human(self) -> bool {
+        self == OutputFormat::Human
+    }
+
+    /// Whether the format is a table of rows, such as CSV.
+    pub fn is_tabular(self) -> bool {
+        matches!(self, OutputFormat::Csv | OutputFormat::Tsv)
+    }
+}
+
+#[derive(Serialize)]
+struct Document<'a, T> {
+    schema_version: u32,
+    kind: &'a str,
+    data: T,
+}
+
+/// Prints a single JSON document, wrapped in the versioned envelope.
+pub fn print_document<T: Serialize>(kind: &str, data: T) {
+    println!("{}", to_document(kind, data));
+}
+
+/// Serializes a JSON document, wrapped in the versioned envelope.
+pub fn to_document<T: Serialize>(kind: &str, data: T) -> String {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        kind,
+        data,
+    };
+    serde_json::to_string(&document).unwrap()
+}
+
+/// Prints the data either as a single JSON document, as one document per record for NDJSON, or as
+/// one row per record for tabular formats.
+pub fn print_records<T: Serialize + Default>(
+    format: OutputFormat,
+    kind: &str,
+    record_kind: &str,
+    records: &[T],
+) -> Result<()> {
+    match format {
+        OutputFormat::Ndjson => records.iter().for_each(|r| print_document(record_kind, r)),
+        OutputFormat::Csv | OutputFormat::Tsv => return print_rows(format, records),
+        _ => print_document(kind, records),
+    }
+    Ok(())
+}
+
+/// Prints the rows as CSV or TSV, with a header row, even when there is no row.
+pub fn print_rows<T: Serialize + Default>(format: OutputFormat, rows: &[T]) -> Result<()> {
+    write_rows(format, rows).map_err(|e| Error::Io {
+        message: "could not write the output".to_string(),
+        source: e.into(),
+    })
+}
+
+fn write_rows<T: Serialize + Default>(format: OutputFormat, rows: &[T]) -> csv::Result<()> {
+    let delimiter = if format == OutputFormat::Tsv {
+        b'\t'
+    } else {
+        b','
+    };
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .from_writer(std::io::stdout());
+
+    writer.write_record(&header::<T>()?)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Field names of the rows of type `T`, taken from the header written for a default row.
+fn header<T: Serialize + Default>() -> csv::Result<csv::StringRecord> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.serialize(T::default())?;
+    let written = writer.into_inner().map_err(|e| e.into_error())?;
+
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(written.as_slice())
+        .records()
+        .next()
+        .unwrap_or_else(|| Ok(csv::StringRecord::new()))
+}
+
+/// A row of the tabular output formats, shared by station boards and train stops.
+#[derive(Default, Serialize)]
+pub struct TableRow {
+    pub train: String,
+    /// Origin or destination for station boards, stop station for train stops
+    pub station: String,
+    pub scheduled_time: String,
+    /// Delay in minutes
+    pub delay: Option<i64>,
+    pub scheduled_platform: Option<String>,
+    pub actual_platform: Option<String>,
+    /// "arrival" or "departure" for station boards, the stop kind for train stops
+    pub kind: &'static str,
+}
+
+impl TableRow {
+    /// Creates one row for each stop of the train.
+    pub fn from_train(train: &TrainReport) -> Vec<Self> {
+        train
+            .stops
+            .iter()
+            .map(|stop| {
+                let delay = if stop.kind == StopKind::Pending.as_str() {
+                    train.delay
+                } else {
+                    stop.delay
+                };
+
+                Self {
+                    train: train.label.clone().unwrap_or_default(),
+                    station: stop.station.clone(),
+                    scheduled_time: format_row_time(
+                        stop.scheduled_arrival.or(stop.scheduled_departure),
+                    ),
+                    delay,
+                    scheduled_platform: stop.scheduled_platform.clone(),
+                    actual_platform: stop.actual_platform.clone(),
+                    kind: stop.kind,
+                }
+            })
+            .collect()
+    }
+
+    pub fn from_board_entry(entry: &BoardEntryReport, kind: &'static str) -> Self {
+        Self {
+            train: entry.label.clone(),
+            station: entry.station.clone().unwrap_or_default(),
+            scheduled_time: format_row_time(entry.scheduled_time),
+            delay: entry.delay,
+            scheduled_platform: entry.scheduled_platform.clone(),
+            actual_platform: entry.actual_platform.clone(),
+            kind,
+        }
+    }
+}
+
+fn format_row_time(time: Option<DateTime<Tz>>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+pub struct TrainReport {
+    pub number: Option<u32>,
+    pub label: Option<String>,
+    pub category: Option<String>,
+    pub origin: Option<String>,
+    pub destination: Option<String>,
+    pub international_origin: Option<String>,
+    pub international_destination: Option<String>,
+    pub canceled: bool,
+    /// One of "regular", "canceled", "partially_canceled", "origin_moved", "limited",
+    /// "rerouted", "unknown"
+    pub run_type: Option<&'static str>,
+    /// Messages about the provisions affecting the run (e.g. "Treno limitato a ROMA TERMINI")
+    pub provisions: Vec<String>,
+    pub departed: bool,
+    pub arrived: bool,
+    /// Delay in minutes
+    pub delay: Option<i64>,
+    pub last_detection_station: Option<String>,
+    pub last_detection_time: Option<DateTime<Tz>>,
+    pub next_stop: Option<String>,
+    pub stops: Vec<StopReport>,
+}
+
+impl From<&TrainStatus> for TrainReport {
+    fn from(status: &TrainStatus) -> Self {
+        let delay = status.delay;
+
+        Self {
+            number: status.number,
+            label: status.label.as_deref().map(|l| l.trim().to_string()),
+            category: status.category.clone(),
+            origin: status.origin.clone(),
+            destination: status.destination.clone(),
+            international_origin: status.international_origin.clone(),
+            international_destination: status.international_destination.clone(),
+            canceled: status.is_canceled(),
+            run_type: status.run_type.as_ref().map(RunType::as_str),
+            provisions: status
+                .provisions()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            departed: !status.is_not_departed(),
+            arrived: status.is_arrived(),
+            delay,
+            last_detection_station: status.last_detection_station.clone(),
+            last_detection_time: status.last_detection_time,
+            next_stop: status.next_stop().map(|s| s.station.clone()),
+            stops: status
+                .stops
+                .iter()
+                .map(|s| StopReport::new(s, delay.unwrap_or(0)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StopReport {
+    pub station: String,
+    pub station_code: String,
+    /// One of "pending", "regular", "extraordinary", "canceled", "unknown"
+    pub kind: &'static str,
+    pub scheduled_arrival: Option<DateTime<Tz>>,
+    pub scheduled_departure: Option<DateTime<Tz>>,
+    pub actual_arrival: Option<DateTime<Tz>>,
+    pub actual_departure: Option<DateTime<Tz>>,
+    /// Estimated times, only set for stops not reached yet
+    pub estimated_arrival: Option<DateTime<Tz>>,
+    pub estimated_departure: Option<DateTime<Tz>>,
+    /// Delay in minutes, only set for stops already reached
+    pub delay: Option<i64>,
+    pub scheduled_platform: Option<String>,
+    pub actual_platform: Option<String>,
+}
+
+impl StopReport {
+    fn new(stop: &Stop, delay: i64) -> Self {
+        let estimate = |time: Option<DateTime<Tz>>| {
+            if stop.kind == StopKind::Pending {
+                time.map(|t| t + TimeDelta::minutes(delay))
+            } else {
+                None
+            }
+        };
+
+        Self {
+            station: stop.station.clone(),
+            station_code: stop.station_code.clone(),
+            kind: stop.kind.as_str(),
+            scheduled_arrival: stop.scheduled_arrival,
+            scheduled_departure: stop.scheduled_departure,
+            actual_arrival: stop.actual_arrival,
+            actual_departure: stop.actual_departure,
+            estimated_arrival: estimate(stop.scheduled_arrival),
+            estimated_departure: estimate(stop.scheduled_departure),
+            delay: stop.delay.filter(|_| stop.kind != StopKind::Pending),
+            scheduled_platform: stop.scheduled_platform().map(str::to_string),
+            actual_platform: stop.actual_platform().map(str::to_string),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StationBoardReport {
+    pub station_code: String,
+    /// Only set when arrivals are requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arrivals: Option<Vec<BoardEntryReport>>,
+    /// Only set when departures are requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departures: Option<Vec<BoardEntryReport>>,
+}
+
+#[derive(Serialize)]
+pub struct BoardEntryReport {
+    pub number: Option<u32>,
+    pub label: String,
+    pub category: Option<String>,
+    /// Origin for arrivals, destination for departures
+    pub station: Option<String>,
+    pub scheduled_time: Option<DateTime<Tz>>,
+    /// Delay in minutes
+    pub delay: Option<i64>,
+    pub scheduled_platform: Option<String>,
+    pub actual_platform: Option<String>,
+}
+
+impl BoardEntryReport {
+    pub fn arrival(entry: &StationBoardEntry) -> Self {
+        Self {
+            number: entry.number,
+            label: entry.label.trim().to_string(),
+            category: entry.category.clone(),
+            station: entry.origin.clone(),
+            scheduled_time: entry.scheduled_arrival,
+            delay: entry.delay,
+            scheduled_platform: non_empty(&entry.scheduled_arrival_platform),
+            actual_platform: non_empty(&entry.actual_arrival_platform),
+        }
+    }
+
+    pub fn departure(entry: &StationBoardEntry) -> Self {
+        Self {
+            number: entry.number,
+            label: entry.label.trim().to_string(),
+            category: entry.category.clone(),
+            station: entry.destination.clone(),
+            scheduled_time: entry.scheduled_departure,
+            delay: entry.delay,
+            scheduled_platform: non_empty(&entry.scheduled_departure_platform),
+            actual_platform: non_empty(&entry.actual_departure_platform),
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+pub struct NewsReport<'a> {
+    pub title: &'a str,
+    pub highlighted: bool,
+    pub body: &'a str,
+}
+
+impl<'a> From<&'a NewsItem> for NewsReport<'a> {
+    fn from(item: &'a NewsItem) -> Self {
+        Self {
+            title: &item.title,
+            highlighted: item.is_highlighted,
+            body: &item.body,
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+pub struct TravelSolutionReport {
+    pub departure: Option<DateTime<Tz>>,
+    pub arrival: Option<DateTime<Tz>>,
+    /// Travel duration, formatted as "HH:MM"
+    pub duration: Option<String>,
+    pub changes: usize,
+    pub legs: Vec<TravelLegReport>,
+}
+
+impl From<&TravelSolution> for TravelSolutionReport {
+    fn from(solution: &TravelSolution) -> Self {
+        Self {
+            departure: local_to_rome(solution.departure()),
+            arrival: local_to_rome(solution.arrival()),
+            duration: solution.duration.clone(),
+            changes: solution.changes(),
+            legs: solution.legs.iter().map(TravelLegReport::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TravelLegReport {
+    pub train_number: String,
+    pub category: Option<String>,
+    pub origin: Option<String>,
+    pub departure: Option<DateTime<Tz>>,
+    pub destination: Option<String>,
+    pub arrival: Option<DateTime<Tz>>,
+}
+
+impl From<&TravelLeg> for TravelLegReport {
+    fn from(leg: &TravelLeg) -> Self {
+        Self {
+            train_number: leg.number.trim().to_string(),
+            category: leg.category.clone(),
+            origin: leg.origin.clone(),
+            departure: local_to_rome(leg.departure),
+            destination: leg.destination.clone(),
+            arrival: local_to_rome(leg.arrival),
+        }
+    }
+}
+
+/// A row of the tabular output of travel solutions, one for each leg.
+#[derive(Default, Serialize)]
+pub struct TravelLegRow {
+    /// 1-based index of the solution the leg belongs to
+    pub solution: usize,
+    pub duration: String,
+    pub changes: usize,
+    pub train_number: String,
+    pub category: String,
+    pub origin: String,
+    pub departure: String,
+    pub destination: String,
+    pub arrival: String,
+}
+
+impl TravelLegRow {
+    pub fn from_solution(index: usize, solution: &TravelSolution) -> Vec<Self> {
+        let format_time = |time: Option<NaiveDateTime>| {
+            time.map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+
+        solution
+            .legs
+            .iter()
+            .map(|leg| Self {
+                solution: index,
+                duration: solution.duration.clone().unwrap_or_default(),
+                changes: solution.changes(),
+                train_number: leg.number.trim().to_string(),
+                category: leg.category.clone().unwrap_or_default(),
+                origin: leg.origin.clone().unwrap_or_default(),
+                departure: format_time(leg.departure),
+                destination: leg.destination.clone().unwrap_or_default(),
+                arrival: format_time(leg.arrival),
+            })
+            .collect()
+    }
+}
+
+/// A train running between two stations, flat so that it can also be printed as a table row.
+#[derive(Default, Serialize)]
+pub struct LineTrainReport {
+    pub number: Option<u32>,
+    pub label: Option<String>,
+    pub category: Option<String>,
+    pub origin: Option<String>,
+    pub destination: Option<String>,
+    /// Stations in the order the train runs through them (e.g. "ROMA TERMINI → FIRENZE S. M. NOVELLA")
+    pub direction: String,
+    pub last_detection_station: Option<String>,
+    pub last_detection_time: Option<DateTime<Tz>>,
+    /// Delay in minutes
+    pub delay: Option<i64>,
+    pub next_stop: Option<String>,
+}
+
+impl LineTrainReport {
+    pub fn new(train: &TrainStatus, direction: &str) -> Self {
+        Self {
+            number: train.number,
+            label: train.label.as_deref().map(|l| l.trim().to_string()),
+            category: train.category.clone(),
+            origin: train.origin.clone(),
+            destination: train.destination.clone(),
+            direction: direction.to_string(),
+            last_detection_station: train.last_detection_station.clone(),
+            last_detection_time: train.last_detection_time,
+            delay: train.delay,
+            next_stop: train.next_stop().map(|s| s.station.clone()),
+        }
+    }
+}
+
+/// A running long distance train.
+#[derive(Default, Serialize)]
+pub struct RunningTrainReport {
+    pub number: Option<u32>,
+    pub label: Option<String>,
+    pub category: Option<String>,
+    pub origin: Option<String>,
+    pub destination: Option<String>,
+    pub last_detection_station: Option<String>,
+    pub last_detection_time: Option<DateTime<Tz>>,
+    /// Delay in minutes
+    pub delay: Option<i64>,
+}
+
+impl From<&TrainStatus> for RunningTrainReport {
+    fn from(train: &TrainStatus) -> Self {
+        Self {
+            number: train.number,
+            label: train.label.as_deref().map(|l| l.trim().to_string()),
+            category: train.category.clone(),
+            origin: train.origin.clone(),
+            destination: train.destination.clone(),
+            last_detection_station: train.last_detection_station.clone(),
+            last_detection_time: train.last_detection_time,
+            delay: train.delay,
+        }
+    }
+}
+
+/// A train running on a regional branch, with its next stop.
+#[derive(Default, Serialize)]
+pub struct BranchTrainReport {
+    pub number: Option<u32>,
+    pub label: Option<String>,
+    pub category: Option<String>,
+    pub origin: Option<String>,
+    pub destination: Option<String>,
+    pub last_detection_station: Option<String>,
+    pub last_detection_time: Option<DateTime<Tz>>,
+    /// Delay in minutes
+    pub delay: Option<i64>,
+    pub next_stop: Option<String>,
+    pub next_stop_scheduled_arrival: Option<DateTime<Tz>>,
+    /// Scheduled arrival at the next stop plus the current delay
+    pub next_stop_estimated_arrival: Option<DateTime<Tz>>,
+}
+
+impl From<&TrainStatus> for BranchTrainReport {
+    fn from(train: &TrainStatus) -> Self {
+        let next_stop = train.next_stop();
+        let scheduled_arrival = next_stop.and_then(|s| s.scheduled_arrival);
+
+        Self {
+            number: train.number,
+            label: train.label.as_deref().map(|l| l.trim().to_string()),
+            category: train.category.clone(),
+            origin: train.origin.clone(),
+            destination: train.destination.clone(),
+            last_detection_station: train.last_detection_station.clone(),
+            last_detection_time: train.last_detection_time,
+            delay: train.delay,
+            next_stop: next_stop.map(|s| s.station.clone()),
+            next_stop_scheduled_arrival: scheduled_arrival,
+            next_stop_estimated_arrival: scheduled_arrival
+                .map(|t| t + TimeDelta::minutes(train.delay.unwrap_or(0))),
+        }
+    }
+}
+
+/// A change in the status of a train, as passed to the `--on-event` command.
+#[derive(Serialize)]
+pub struct TrainEventReport {
+    /// Kind of event (e.g. "platform_changed")
+    pub event: &'static str,
+    /// Human readable description of the event
+    pub message: String,
+    pub train_number: Option<u32>,
+    pub train_label: Option<String>,
+    pub station: Option<String>,
+    /// Current delay of the train in minutes
+    pub delay: Option<i64>,
+    /// Delay before the change, for "delay_changed" events
+    pub previous_delay: Option<i64>,
+    /// Delay reached, for "delay_threshold_crossed" events
+    pub threshold: Option<i64>,
+    pub platform: Option<String>,
+    /// Platform before the change, for "platform_changed" events
+    pub previous_platform: Option<String>,
+    pub detected_at: DateTime<Tz>,
+}
+
+impl TrainEventReport {
+    pub fn new(event: &TrainEvent, train: &TrainStatus, detected_at: DateTime<Tz>) -> Self {
+        let mut report = Self {
+            event: event.kind(),
+            message: event.to_string(),
+            train_number: train.number,
+            train_label: train.label.as_deref().map(|l| l.trim().to_string()),
+            station: event.station().map(str::to_string),
+            delay: train.delay,
+            previous_delay: None,
+            threshold: None,
+            platform: None,
+            previous_platform: None,
+            detected_at,
+        };
+
+        match event {
+            TrainEvent::DelayChanged { from, .. } => report.previous_delay = Some(*from),
+            TrainEvent::DelayThresholdCrossed { threshold, .. } => {
+                report.threshold = Some(*threshold)
+            }
+            TrainEvent::PlatformAssigned { platform, .. } => {
+                report.platform = Some(platform.clone())
+            }
+            TrainEvent::PlatformChanged { from, to, .. } => {
+                report.platform = Some(to.clone());
+                report.previous_platform = Some(from.clone());
+            }
+            _ => {}
+        }
+
+        report
+    }
+}
+
+/// A change between two trains, with the estimated transfer time.
+#[derive(Default, Serialize)]
+pub struct ConnectionReport {
+    pub station: String,
+    pub station_code: String,
+    pub arriving_train: Option<String>,
+    pub scheduled_arrival: Option<DateTime<Tz>>,
+    pub estimated_arrival: Option<DateTime<Tz>>,
+    pub arrival_platform: Option<String>,
+    pub departing_train: Option<String>,
+    pub scheduled_departure: Option<DateTime<Tz>>,
+    pub estimated_departure: Option<DateTime<Tz>>,
+    pub departure_platform: Option<String>,
+    /// Estimated transfer time in minutes
+    pub transfer: Option<i64>,
+    /// Minimum transfer time in minutes
+    pub min_transfer: i64,
+    /// "make" or "miss"
+    pub verdict: &'static str,
+}
+
+fn local_to_rome(time: Option<NaiveDateTime>) -> Option<DateTime<Tz>> {
+    time.and_then(|t| Rome.from_local_datetime(&t).earliest())
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
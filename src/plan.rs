@@ -0,0 +1,156 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use colored::Colorize;
+use viaggiatreno::{Error, Result, TravelLeg, TravelSolution, time};
+
+use crate::cli_input;
+use crate::context::Context;
+use crate::output::{self, TravelLegRow, TravelSolutionReport};
+use crate::station;
+use crate::track_train::{self, TrainSelection};
+
+pub async fn plan(ctx: &Context, from: &str, to: &str, at: Option<NaiveDateTime>) -> Result<()> {
+    let from_station = station::find_station(ctx, from).await?;
+    let to_station = station::find_station(ctx, to).await?;
+
+    let at = at.unwrap_or_else(|| time::now().naive_local());
+
+    let res = ctx
+        .client
+        .travel_solutions(&from_station.code, &to_station.code, at)
+        .await?;
+    let solutions = res.solutions;
+
+    if ctx.output.is_tabular() {
+        let rows: Vec<_> = solutions
+            .iter()
+            .enumerate()
+            .flat_map(|(i, solution)| TravelLegRow::from_solution(i + 1, solution))
+            .collect();
+        return output::print_rows(ctx.output, &rows);
+    } else if !ctx.output.is_human() {
+        let reports: Vec<_> = solutions.iter().map(TravelSolutionReport::from).collect();
+        return output::print_records(ctx.output, "travel_solutions", "travel_solution", &reports);
+    }
+
+    if solutions.is_empty() {
+        return Err(Error::NotFound(format!(
+            "No travel solution found from {} to {}.",
+            from_station.name, to_station.name
+        )));
+    }
+
+    println!(
+        "Travel solutions from {} to {}, departing after {}:\n",
+        res.origin.as_deref().unwrap_or(&from_station.name).cyan(),
+        res.destination
+            .as_deref()
+            .unwrap_or(&to_station.name)
+            .cyan(),
+        at.format("%d/%m %H:%M")
+    );
+
+    let day = at.date();
+    for (i, solution) in solutions.iter().enumerate() {
+        print_solution(i + 1, solution, day);
+    }
+
+    let legs: Vec<&TravelLeg> = solutions.iter().flat_map(|s| &s.legs).collect();
+
+    if !ctx.interactive {
+        println!(
+            "{}",
+            "Track one of the trains with: infotreno-cli track <train number>".dimmed()
+        );
+        return Ok(());
+    }
+
+    let leg_descriptions: Vec<_> = legs.iter().map(|leg| describe_leg(leg, day)).collect();
+    let Some(index) = cli_input::prompt_selection("Select a train to track:", &leg_descriptions)?
+    else {
+        return Ok(());
+    };
+
+    let leg = legs[index];
+    let number: u32 =
+        leg.number.trim().parse().map_err(|_| {
+            Error::MalformedResponse(format!("invalid train number: {}", leg.number))
+        })?;
+    // The run of the day of the leg, which may not be today with --at. The origin of the leg is
+    // where the user boards, not necessarily the origin of the train, so it does not select a run.
+    let selection = TrainSelection {
+        date: leg.departure.map(|d| d.date()),
+        ..TrainSelection::default()
+    };
+
+    println!();
+    track_train::track(ctx, &[number.into()], &selection, None, None).await
+}
+
+fn print_solution(index: usize, solution: &TravelSolution, day: NaiveDate) {
+    let changes = match solution.changes() {
+        0 => "direct".to_string(),
+        1 => "1 change".to_string(),
+        n => format!("{n} changes"),
+    };
+
+    println!(
+        "{}. {} - {}  ({}, {})",
+        index,
+        format_time(solution.departure(), day).bold(),
+        format_time(solution.arrival(), day).bold(),
+        solution.duration.as_deref().unwrap_or("--:--"),
+        changes
+    );
+
+    for leg in &solution.legs {
+        println!("\t{}", describe_leg(leg, day));
+    }
+    println!();
+}
+
+fn describe_leg(leg: &TravelLeg, day: NaiveDate) -> String {
+    format!(
+        "{} {}  {} {} - {} {}",
+        leg.category.as_deref().unwrap_or_default(),
+        leg.number.trim(),
+        leg.origin.as_deref().unwrap_or("--"),
+        format_time(leg.departure, day),
+        leg.destination.as_deref().unwrap_or("--"),
+        format_time(leg.arrival, day)
+    )
+}
+
+/// Formats a time as "HH:MM", with the date when it is not on `day`, the day of the search.
+fn format_time(time: Option<NaiveDateTime>, day: NaiveDate) -> String {
+    time.map(|t| {
+        if t.date() == day {
+            t.format("%H:%M").to_string()
+        } else {
+            t.format("%d/%m %H:%M").to_string()
+        }
+    })
+    .unwrap_or("--:--".to_string())
+}
+
+/// Parses the departure time of the `plan` command: "YYYY-MM-DD HH:MM", or "HH:MM" for today.
+pub fn parse_datetime(s: &str) -> std::result::Result<NaiveDateTime, String> {
+    let s = s.trim();
+
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(datetime);
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN));
+    }
+
+    if let Ok(time) = NaiveTime::parse_from_str(s, "%H:%M") {
+        return Ok(time::today().and_time(time));
+    }
+
+    Err(format!(
+        "\"{s}\" is not valid, expected \"YYYY-MM-DD HH:MM\", \"YYYY-MM-DD\" or \"HH:MM\""
+    ))
+}
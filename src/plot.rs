@@ -0,0 +1,195 @@
+use chrono::{DateTime, TimeDelta};
+use chrono_tz::Tz;
+use colored::Colorize;
+use viaggiatreno::TrainStatus;
+
+use crate::history::ON_TIME_THRESHOLD;
+use crate::line::LineTrain;
+
+/// Number of empty rows drawn between two consecutive stations.
+const ROWS_BETWEEN_STATIONS: usize = 3;
+
+/// Characters used to draw a line diagram.
+pub struct Charset {
+    top: char,
+    station: char,
+    bottom: char,
+    track: char,
+    forward: char,
+    backward: char,
+}
+
+impl Charset {
+    pub const UNICODE: Self = Self {
+        top: '┬',
+        station: '┼',
+        bottom: '┴',
+        track: '│',
+        forward: '▼',
+        backward: '▲',
+    };
+
+    pub const ASCII: Self = Self {
+        top: '+',
+        station: '+',
+        bottom: '+',
+        track: '|',
+        forward: 'v',
+        backward: '^',
+    };
+}
+
+/// Draws the line between two stations from top to bottom, with a tick for each station and the
+/// trains running on it at their estimated position.
+/// The stations are taken from the train stopping most often between the two; with no trains,
+/// only the two stations are drawn.
+pub fn render(
+    from: (&str, &str),
+    to: (&str, &str),
+    trains: &[LineTrain],
+    now: DateTime<Tz>,
+    charset: &Charset,
+) -> String {
+    let stations = line_stations(from, to, trains);
+    let codes: Vec<_> = stations.iter().map(|(code, _)| *code).collect();
+
+    let row_count = (stations.len() - 1) * (ROWS_BETWEEN_STATIONS + 1) + 1;
+    let mut labels = vec![Vec::new(); row_count];
+    for line_train in trains {
+        let Some(position) = train_position(&line_train.train, &codes, now) else {
+            continue;
+        };
+        let row = (position * (ROWS_BETWEEN_STATIONS + 1) as f64).round() as usize;
+        labels[row.min(row_count - 1)].push(train_label(line_train, charset));
+    }
+
+    let mut diagram = String::new();
+    for (row, row_labels) in labels.iter().enumerate() {
+        let trains = row_labels.join("  ");
+        if row % (ROWS_BETWEEN_STATIONS + 1) == 0 {
+            let tick = match row {
+                0 => charset.top,
+                r if r == row_count - 1 => charset.bottom,
+                _ => charset.station,
+            };
+            let (_, name) = stations[row / (ROWS_BETWEEN_STATIONS + 1)];
+            diagram.push_str(&format!(" {}  {}", tick, name.cyan()));
+            if !trains.is_empty() {
+                diagram.push_str(&format!("  {}", trains));
+            }
+        } else {
+            diagram.push_str(&format!(" {}", charset.track));
+            if !trains.is_empty() {
+                diagram.push_str(&format!("    {}", trains));
+            }
+        }
+        diagram.push('\n');
+    }
+
+    diagram
+}
+
+/// Codes and names of the stations drawn on the line, from `from` to `to`.
+fn line_stations<'a>(
+    from: (&'a str, &'a str),
+    to: (&'a str, &'a str),
+    trains: &'a [LineTrain],
+) -> Vec<(&'a str, &'a str)> {
+    let stops = trains
+        .iter()
+        .filter_map(|t| {
+            let from_index = t.train.stop_index(from.0)?;
+            let to_index = t.train.stop_index(to.0)?;
+            Some((t, from_index, to_index))
+        })
+        .max_by_key(|(_, from_index, to_index)| from_index.abs_diff(*to_index));
+
+    let Some((line_train, from_index, to_index)) = stops else {
+        return vec![from, to];
+    };
+
+    let stops = &line_train.train.stops[from_index.min(to_index)..=from_index.max(to_index)];
+    let mut stations: Vec<_> = stops
+        .iter()
+        .map(|s| (s.station_code.as_str(), s.station.as_str()))
+        .collect();
+    if from_index > to_index {
+        stations.reverse();
+    }
+    stations
+}
+
+/// Position of a train along the line, as a fractional index into `station_codes`.
+/// The train is placed between the last stop it reached and the next one according to its
+/// schedule, shifted by the delay measured at its last detection.
+fn train_position(train: &TrainStatus, station_codes: &[&str], now: DateTime<Tz>) -> Option<f64> {
+    let reached = train.last_reached_stop_index()?;
+    let line_index = |i: usize| {
+        station_codes
+            .iter()
+            .position(|code| *code == train.stops[i].station_code)
+    };
+
+    // Closest stops on the drawn line before and after the current position of the train, as
+    // the train may stop at stations not drawn
+    let (before, line_before) = (0..=reached)
+        .rev()
+        .find_map(|i| line_index(i).map(|l| (i, l)))?;
+    let (after, line_after) =
+        (reached + 1..train.stops.len()).find_map(|i| line_index(i).map(|l| (i, l)))?;
+
+    let progress = (reached - before) as f64 + segment_progress(train, reached, now);
+    let fraction = progress / (after - before) as f64;
+
+    Some(line_before as f64 + fraction * (line_after as f64 - line_before as f64))
+}
+
+/// Progress of a train from the stop at `index` to the next one, between 0 and 1.
+fn segment_progress(train: &TrainStatus, index: usize, now: DateTime<Tz>) -> f64 {
+    let stop = &train.stops[index];
+    // Still standing at the station
+    if stop.actual_departure.is_none() {
+        return 0.0;
+    }
+
+    let (Some(departure), Some(arrival)) = (
+        stop.scheduled_departure,
+        train.stops.get(index + 1).and_then(|s| s.scheduled_arrival),
+    ) else {
+        return 0.0;
+    };
+
+    let scheduled_now = now - TimeDelta::minutes(train.delay.unwrap_or(0));
+    let total = (arrival - departure).num_seconds();
+    if total <= 0 {
+        return 1.0;
+    }
+
+    ((scheduled_now - departure).num_seconds() as f64 / total as f64).clamp(0.0, 1.0)
+}
+
+fn train_label(line_train: &LineTrain, charset: &Charset) -> String {
+    let train = &line_train.train;
+    let arrow = if line_train.forward {
+        charset.forward
+    } else {
+        charset.backward
+    };
+    let delay = train.delay.unwrap_or(0);
+    let label = format!(
+        "{} {} {}",
+        arrow,
+        train.label.as_deref().unwrap_or("--").trim(),
+        if delay > 0 {
+            format!("+{delay}")
+        } else {
+            delay.to_string()
+        }
+    );
+
+    if delay <= ON_TIME_THRESHOLD {
+        label.green().to_string()
+    } else {
+        label.red().to_string()
+    }
+}
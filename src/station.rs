@@ -1,22 +1,29 @@
-use chrono::Utc;
-use chrono_tz::Europe::Rome;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
 use colored::Colorize;
 use regex::Regex;
-use tabular::{Table, row};
+use tabular::{Row, Table};
+use viaggiatreno::{Error, Result, StationBoardEntry, StationCandidate, time};
 
-use crate::cli_input;
+use crate::cli_input::{self, Pick};
+use crate::context::Context;
+use crate::history;
+use crate::output::{self, BoardEntryReport, OutputFormat, StationBoardReport, TableRow};
+use crate::track_train;
+use crate::watch;
 
 pub async fn station(
+    ctx: &Context,
     name: &str,
     print_arrivals: bool,
     print_departures: bool,
     filter: Option<&str>,
-) -> Result<(), reqwest::Error> {
-    let timestamp = Utc::now()
-        .with_timezone(&Rome)
-        .format("%b %d %Y %H:%M:%S")
-        .to_string();
-
+    pick: Option<Pick>,
+    watch: Option<Duration>,
+) -> Result<()> {
     // If both print_arrivals and print_departures are false, print both
     let (print_arrivals, print_departures) = if !(print_arrivals || print_departures) {
         (true, true)
@@ -24,115 +31,242 @@ pub async fn station(
         (print_arrivals, print_departures)
     };
 
-    let re = Regex::new(r"S[0-9]{5}").unwrap();
+    let station = search_station(
+        ctx,
+        name,
+        pick,
+        "Use --pick to select one non-interactively.",
+    )
+    .await?;
+
+    let Some(interval) = watch else {
+        print_station_arrivals_departures(
+            ctx,
+            &station,
+            print_arrivals,
+            print_departures,
+            filter,
+            None,
+            None,
+        )
+        .await?;
+        return Ok(());
+    };
 
-    if re.is_match(name) {
-        return print_station_arrivals_departures(
-            name,
-            &timestamp,
+    let mut previous = None;
+    watch::run(interval, async || {
+        let snapshot = print_station_arrivals_departures(
+            ctx,
+            &station,
             print_arrivals,
             print_departures,
             filter,
+            watch,
+            previous.as_ref(),
         )
-        .await;
+        .await?;
+        previous = Some(snapshot);
+        Ok(())
+    })
+    .await
+}
+
+/// Delays and platforms of the trains on the boards of a station, used in watch mode to highlight
+/// the trains whose delay or platform changed since the previous refresh.
+#[derive(Default)]
+struct BoardSnapshot {
+    trains: HashMap<BoardKey, (Option<i64>, String)>,
+}
+
+/// Board ("arrival" or "departure"), train label and departure date of a train.
+type BoardKey = (&'static str, String, Option<DateTime<Tz>>);
+
+impl BoardSnapshot {
+    fn insert(&mut self, board: &'static str, train: &StationBoardEntry, platform: &str) {
+        self.trains
+            .insert(board_key(board, train), (train.delay, platform.to_string()));
     }
 
-    let url = format!(
-        "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/autocompletaStazione/{}",
-        name.trim()
-    );
+    /// Whether the train was on the board with a different delay or platform.
+    fn has_changed(&self, board: &'static str, train: &StationBoardEntry, platform: &str) -> bool {
+        self.trains
+            .get(&board_key(board, train))
+            .is_some_and(|(delay, previous_platform)| {
+                *delay != train.delay || previous_platform != platform
+            })
+    }
+}
 
-    let res = reqwest::get(url).await?.text().await?;
-    let lines: Vec<(&str, &str)> = res
-        .lines()
-        .map(|l| {
-            let mut line_section = l.split('|');
-            (line_section.next().unwrap(), line_section.next().unwrap())
-        })
-        .collect();
+fn board_key(board: &'static str, train: &StationBoardEntry) -> BoardKey {
+    (board, train.label.trim().to_string(), train.departure_date)
+}
 
-    if lines.is_empty() {
-        println!("No station found with the name provided.");
-        return Ok(());
+/// Hint given when several stations match the name and the user cannot be asked to choose one.
+const STATION_CODE_HINT: &str =
+    "Use the station code (e.g. S05043) to select one non-interactively.";
+
+/// Finds a station by its code (e.g. "S01700") or by the beginning of its name, asking the user to
+/// choose one when several stations match the name.
+pub async fn find_station(ctx: &Context, name: &str) -> Result<StationCandidate> {
+    search_station(ctx, name, None, STATION_CODE_HINT).await
+}
+
+async fn search_station(
+    ctx: &Context,
+    name: &str,
+    pick: Option<Pick>,
+    hint: &str,
+) -> Result<StationCandidate> {
+    if is_station_code(name) {
+        return Ok(StationCandidate {
+            name: name.to_string(),
+            code: name.to_string(),
+        });
     }
 
-    let index = if lines.len() > 1 {
-        println!(
-            "Found more than one station with the name provided. Please select the desired one:"
-        );
+    let lines = ctx.client.autocomplete_station(name).await?;
+    select_station(ctx, name, lines, pick, hint)
+}
 
-        lines.iter().enumerate().for_each(|(i, (name, code))| {
-            println!("{}. {} ({})", i + 1, name.bold(), code);
+/// Finds a station like [`find_station`], but picks the station whose name is exactly the given
+/// one when there is one among the candidates, without asking.
+pub async fn find_station_exact(ctx: &Context, name: &str) -> Result<StationCandidate> {
+    if is_station_code(name) {
+        return Ok(StationCandidate {
+            name: name.to_string(),
+            code: name.to_string(),
         });
+    }
 
-        cli_input::get_index() - 1
-    } else {
-        0
-    };
+    let mut lines = ctx.client.autocomplete_station(name).await?;
+    if let Some(index) = lines
+        .iter()
+        .position(|station| station.name.trim().eq_ignore_ascii_case(name.trim()))
+    {
+        return Ok(lines.swap_remove(index));
+    }
 
-    if index >= lines.len() {
-        eprintln!("Invalid index.");
-        return Ok(());
+    select_station(ctx, name, lines, None, STATION_CODE_HINT)
+}
+
+pub fn is_station_code(name: &str) -> bool {
+    let re = Regex::new(r"S[0-9]{5}").unwrap();
+    re.is_match(name)
+}
+
+fn select_station(
+    ctx: &Context,
+    name: &str,
+    mut lines: Vec<StationCandidate>,
+    pick: Option<Pick>,
+    hint: &str,
+) -> Result<StationCandidate> {
+    if lines.is_empty() {
+        return Err(Error::NotFound(format!(
+            "No station found with the name \"{}\".",
+            name.trim()
+        )));
     }
 
-    print_station_arrivals_departures(
-        lines[index].1,
-        &timestamp,
-        print_arrivals,
-        print_departures,
-        filter,
-    )
-    .await
+    let candidates: Vec<_> = lines
+        .iter()
+        .map(|station| format!("{} ({})", station.name, station.code))
+        .collect();
+    let index = cli_input::select(
+        ctx,
+        &format!("Found more than one station matching \"{}\".", name.trim()),
+        &candidates,
+        pick,
+        Some(hint),
+    )?;
+
+    Ok(lines.swap_remove(index))
 }
 
 async fn print_station_arrivals_departures(
-    station_code: &str,
-    timestamp: &str,
+    ctx: &Context,
+    station: &StationCandidate,
     print_arrivals: bool,
     print_departures: bool,
     filter: Option<&str>,
-) -> Result<(), reqwest::Error> {
-    let filter_train_type = |train: &serde_json::Value| {
-        if filter.is_none() {
-            return true;
-        }
-
-        let train_type = train["categoriaDescrizione"].as_str().unwrap_or_default();
-        filter.unwrap().trim().to_lowercase() == train_type.trim().to_lowercase()
+    watch: Option<Duration>,
+    previous: Option<&BoardSnapshot>,
+) -> Result<BoardSnapshot> {
+    let filter_train_type =
+        |train: &StationBoardEntry| filter.is_none_or(|category| train.has_category(category));
+
+    let station_code = station.code.as_str();
+    let timestamp = time::now();
+
+    let arrivals = if print_arrivals {
+        let arrivals = ctx.client.arrivals(station_code, timestamp).await?;
+        Some(
+            arrivals
+                .into_iter()
+                .filter(filter_train_type)
+                .collect::<Vec<_>>(),
+        )
+    } else {
+        None
+    };
+    let departures = if print_departures {
+        let departures = ctx.client.departures(station_code, timestamp).await?;
+        Some(
+            departures
+                .into_iter()
+                .filter(filter_train_type)
+                .collect::<Vec<_>>(),
+        )
+    } else {
+        None
     };
 
-    if print_arrivals {
-        let url = format!(
-            "http://www.viaggiatreno.it/infomobilitamobile/resteasy/viaggiatreno/arrivi/{}/{}",
-            station_code, timestamp
-        );
+    history::record_board(
+        ctx,
+        station,
+        arrivals.as_deref().unwrap_or_default(),
+        departures.as_deref().unwrap_or_default(),
+    );
+
+    let mut snapshot = BoardSnapshot::default();
 
-        let res = reqwest::get(url).await?.json::<serde_json::Value>().await?;
+    if !ctx.output.is_human() {
+        print_machine_readable(ctx.output, station_code, arrivals, departures)?;
+        return Ok(snapshot);
+    }
+
+    if let Some(interval) = watch {
+        watch::clear_screen(interval);
+    }
 
-        let arrivals = res.as_array().unwrap();
+    let highlight = |cell: String, changed: bool| {
+        if changed {
+            cell.black().on_yellow().to_string()
+        } else {
+            cell
+        }
+    };
 
+    if let Some(arrivals) = arrivals {
         println!("\t----  {}  -----", "Arrivals".bold().green());
 
         let mut arrivals_table = Table::new("{:<}  {:<} {:>} {:<}  {:<}");
 
-        for train in arrivals.iter().filter(|t| filter_train_type(t)) {
-            let train_label = train["compNumeroTreno"].as_str().unwrap().trim();
-            let origin = train["origine"].as_str().unwrap();
-            let arrival_time = train["compOrarioArrivo"].as_str().unwrap();
-            let delay_number = train["ritardo"].as_i64().unwrap_or(0);
-            let delay = match delay_number {
-                0 => "".to_string(),
-                n if n > 0 => format!("+{}", n),
-                n => n.to_string(),
-            };
+        for train in &arrivals {
+            let train_label = train.label.trim();
+            let origin = train.origin.as_deref().unwrap_or("--");
+            let arrival_time = board_time(train.scheduled_arrival, train.arrival_time.as_deref());
+            let delay = format_delay(train.delay.unwrap_or(0));
 
-            let scheduled_platform = train["binarioProgrammatoArrivoDescrizione"]
-                .as_str()
+            let scheduled_platform = train
+                .scheduled_arrival_platform
+                .as_deref()
                 .unwrap_or("")
                 .trim();
 
-            let actual_platform = train["binarioEffettivoArrivoDescrizione"]
-                .as_str()
+            let actual_platform = train
+                .actual_arrival_platform
+                .as_deref()
                 .unwrap_or("")
                 .trim();
 
@@ -142,48 +276,47 @@ async fn print_station_arrivals_departures(
                 actual_platform.green().to_string()
             };
 
-            arrivals_table.add_row(row!(
-                train_label.bold(),
-                origin,
-                arrival_time,
-                delay,
-                platform
-            ));
+            let current_platform = if actual_platform.is_empty() {
+                scheduled_platform
+            } else {
+                actual_platform
+            };
+            let changed =
+                previous.is_some_and(|p| p.has_changed("arrival", train, current_platform));
+            snapshot.insert("arrival", train, current_platform);
+
+            arrivals_table.add_row(
+                Row::new()
+                    .with_ansi_cell(highlight(train_label.bold().to_string(), changed))
+                    .with_ansi_cell(highlight(origin.to_string(), changed))
+                    .with_ansi_cell(highlight(arrival_time, changed))
+                    .with_ansi_cell(highlight(delay, changed))
+                    .with_ansi_cell(highlight(platform, changed)),
+            );
         }
         println!("{arrivals_table}");
     }
-    if print_departures {
-        let url = format!(
-            "http://www.viaggiatreno.it/infomobilitamobile/resteasy/viaggiatreno/partenze/{}/{}",
-            station_code, timestamp
-        );
-
-        let res = reqwest::get(url).await?.json::<serde_json::Value>().await?;
-
-        let departures = res.as_array().unwrap();
-
+    if let Some(departures) = departures {
         println!("\t---- {} -----", "Departures".bold().magenta());
 
         let mut departures_table = Table::new("{:<}  {:<} {:>} {:<}  {:<}");
 
-        for train in departures.iter().filter(|t| filter_train_type(t)) {
-            let train_label = train["compNumeroTreno"].as_str().unwrap().trim();
-            let destination = train["destinazione"].as_str().unwrap();
-            let departure_time = train["compOrarioPartenza"].as_str().unwrap();
-            let delay_number = train["ritardo"].as_i64().unwrap_or(0);
-            let delay = match delay_number {
-                0 => "".to_string(),
-                n if n > 0 => format!("+{}", n),
-                n => n.to_string(),
-            };
+        for train in &departures {
+            let train_label = train.label.trim();
+            let destination = train.destination.as_deref().unwrap_or("--");
+            let departure_time =
+                board_time(train.scheduled_departure, train.departure_time.as_deref());
+            let delay = format_delay(train.delay.unwrap_or(0));
 
-            let scheduled_platform = train["binarioProgrammatoPartenzaDescrizione"]
-                .as_str()
+            let scheduled_platform = train
+                .scheduled_departure_platform
+                .as_deref()
                 .unwrap_or("")
                 .trim();
 
-            let actual_platform = train["binarioEffettivoPartenzaDescrizione"]
-                .as_str()
+            let actual_platform = train
+                .actual_departure_platform
+                .as_deref()
                 .unwrap_or("")
                 .trim();
 
@@ -193,17 +326,82 @@ async fn print_station_arrivals_departures(
                 actual_platform.green().to_string()
             };
 
-            departures_table.add_row(row!(
-                train_label.bold(),
-                destination,
-                departure_time,
-                delay,
-                platform
-            ));
+            let current_platform = if actual_platform.is_empty() {
+                scheduled_platform
+            } else {
+                actual_platform
+            };
+            let changed =
+                previous.is_some_and(|p| p.has_changed("departure", train, current_platform));
+            snapshot.insert("departure", train, current_platform);
+
+            departures_table.add_row(
+                Row::new()
+                    .with_ansi_cell(highlight(train_label.bold().to_string(), changed))
+                    .with_ansi_cell(highlight(destination.to_string(), changed))
+                    .with_ansi_cell(highlight(departure_time, changed))
+                    .with_ansi_cell(highlight(delay, changed))
+                    .with_ansi_cell(highlight(platform, changed)),
+            );
         }
 
         println!("{departures_table}");
     }
 
+    Ok(snapshot)
+}
+
+fn print_machine_readable(
+    format: OutputFormat,
+    station_code: &str,
+    arrivals: Option<Vec<StationBoardEntry>>,
+    departures: Option<Vec<StationBoardEntry>>,
+) -> Result<()> {
+    let report = StationBoardReport {
+        station_code: station_code.to_string(),
+        arrivals: arrivals.map(|a| a.iter().map(BoardEntryReport::arrival).collect()),
+        departures: departures.map(|d| d.iter().map(BoardEntryReport::departure).collect()),
+    };
+
+    if format.is_tabular() {
+        let arrivals = report.arrivals.iter().flatten();
+        let departures = report.departures.iter().flatten();
+        let rows: Vec<_> = arrivals
+            .map(|entry| TableRow::from_board_entry(entry, "arrival"))
+            .chain(departures.map(|entry| TableRow::from_board_entry(entry, "departure")))
+            .collect();
+        return output::print_rows(format, &rows);
+    } else if format == OutputFormat::Ndjson {
+        let records = [
+            ("arrival", report.arrivals.unwrap_or_default()),
+            ("departure", report.departures.unwrap_or_default()),
+        ];
+        for (kind, entries) in records {
+            entries
+                .iter()
+                .for_each(|entry| output::print_document(kind, entry));
+        }
+    } else {
+        output::print_document("station_board", report);
+    }
+
     Ok(())
 }
+
+/// Scheduled time of a board entry, with its date when it is not today, or the "HH:MM" time
+/// given by the board when the full time is missing.
+fn board_time(time: Option<DateTime<Tz>>, formatted: Option<&str>) -> String {
+    match (time, formatted) {
+        (Some(time), _) => track_train::format_time(Some(time)),
+        (None, Some(formatted)) => formatted.to_string(),
+        (None, None) => "--:--".to_string(),
+    }
+}
+
+pub fn format_delay(delay: i64) -> String {
+    match delay {
+        0 => "".to_string(),
+        n if n > 0 => format!("+{}", n),
+        n => n.to_string(),
+    }
+}
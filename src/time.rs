@@ -0,0 +1,54 @@
+//! Times returned by the API, converted to the Europe/Rome timezone.
+//!
+//! The API returns instants as milliseconds since the Unix epoch. They are converted to full dates
+//! in the Italian timezone, so that the UTC offset is the one in effect at that instant (CET or
+//! CEST) rather than the current one, and trains running overnight keep their date.
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::{Europe::Rome, Tz};
+
+/// Converts milliseconds since the Unix epoch to a date and time in the Europe/Rome timezone.
+pub fn from_milliseconds(milliseconds: i64) -> Option<DateTime<Tz>> {
+    Rome.timestamp_millis_opt(milliseconds).single()
+}
+
+/// Current date and time in the Europe/Rome timezone.
+pub fn now() -> DateTime<Tz> {
+    Utc::now().with_timezone(&Rome)
+}
+
+/// Current date in the Europe/Rome timezone.
+pub fn today() -> NaiveDate {
+    now().date_naive()
+}
+
+/// Formats a time as "HH:MM", prefixed by the day and month ("dd/mm HH:MM") when it is not on
+/// `today`.
+pub fn format_time(time: DateTime<Tz>, today: NaiveDate) -> String {
+    if time.date_naive() == today {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%d/%m %H:%M").to_string()
+    }
+}
+
+/// Deserializes optional milliseconds since the Unix epoch to a date and time in the Europe/Rome
+/// timezone, for use with `#[serde(with = "milliseconds_option")]`.
+pub(crate) mod milliseconds_option {
+    use chrono::DateTime;
+    use chrono_tz::Tz;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Tz>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<i64>::deserialize(deserializer)? {
+            Some(milliseconds) => super::from_milliseconds(milliseconds)
+                .map(Some)
+                .ok_or_else(|| D::Error::custom(format!("timestamp out of range: {milliseconds}"))),
+            None => Ok(None),
+        }
+    }
+}
@@ -1,97 +1,581 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
 use std::time::Duration;
 
-use chrono::{Local, NaiveTime, Offset, TimeZone};
-use chrono_tz::Europe::Rome;
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, TimeZone};
+use chrono_tz::{Europe::Rome, Tz};
 use colored::Colorize;
-use serde_json::Value;
+use futures::future::join_all;
+use tabular::{Table, row};
+use viaggiatreno::estimate::RecoveryModel;
+use viaggiatreno::events::DiffOptions;
+use viaggiatreno::{
+    Error, Result, RunType, StopKind, TrainCandidate, TrainEvent, TrainStatus, events, time,
+};
+
+use crate::cli_input::{self, Pick};
+use crate::context::Context;
+use crate::history;
+use crate::hooks::{self, Webhook};
+use crate::output::{self, OutputFormat, TableRow, TrainEventReport, TrainReport};
+use crate::station;
+use crate::watch;
+
+/// Tracks one or more trains. `stops` selects the trains whose stops are printed: all of them
+/// when it is empty, none when it is `None`.
+pub async fn track(
+    ctx: &Context,
+    codes: &[TrainId],
+    selection: &TrainSelection,
+    stops: Option<&[u32]>,
+    watch: Option<WatchOptions>,
+) -> Result<()> {
+    // The index of a candidate is only meaningful for the candidates of one code
+    let hint = if codes.len() > 1 {
+        if selection.index.is_some() {
+            return Err(Error::InvalidInput(
+                "--index can only be used when tracking a single train.".to_string(),
+            ));
+        }
+        "Use --origin or --date to select one non-interactively."
+    } else {
+        "Use --index, --origin or --date to select one non-interactively."
+    };
 
-use crate::cli_input;
+    // Trains are looked up one at a time, as the user may be asked to choose among candidates
+    let mut trains = Vec::with_capacity(codes.len());
+    for code in codes {
+        let mut train = find_train(ctx, code, selection, Some(hint)).await?;
+        train.print_stops = stops.is_some_and(|s| s.is_empty() || s.contains(&code.number));
+        trains.push(train);
+    }
 
-pub async fn track(
-    code: u32,
-    index: Option<usize>,
-    print_stops: bool,
-    auto_refresh: bool,
-) -> Result<(), reqwest::Error> {
-    let url = format!(
-        "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/cercaNumeroTrenoTrenoAutocomplete/{}",
-        code
-    );
+    match watch {
+        Some(options) => {
+            let interval = options.interval;
+            let mut state = TrackWatch::new(options, trains.len());
+            watch::run(interval, async || {
+                print_train_track_info(ctx, &trains, Some(&mut state)).await
+            })
+            .await
+        }
+        None => print_train_track_info(ctx, &trains, None).await,
+    }
+}
 
-    let res = reqwest::get(url).await?.text().await?;
+/// A train code as written on tickets and boards: a number, optionally preceded by the train
+/// category (e.g. "9612" or "FR 9612").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrainId {
+    pub category: Option<String>,
+    pub number: u32,
+}
+
+impl From<u32> for TrainId {
+    fn from(number: u32) -> Self {
+        Self {
+            category: None,
+            number,
+        }
+    }
+}
+
+impl FromStr for TrainId {
+    type Err = String;
 
-    let lines: Vec<_> = res.lines().collect();
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || {
+            format!("\"{s}\" is not valid, expected a train number such as \"9612\" or \"FR 9612\"")
+        };
+
+        let digits = s.find(|c: char| c.is_ascii_digit()).ok_or_else(invalid)?;
+        let (category, number) = s.split_at(digits);
+        let category = category.trim();
+        if !category.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            category: (!category.is_empty()).then(|| category.to_ascii_uppercase()),
+            number: number.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl TrainId {
+    /// Parses the train codes given on the command line, where a code written without quotes
+    /// (e.g. `track FR 9612`) is split in its category and its number.
+    pub fn parse_all(args: &[String]) -> Result<Vec<Self>> {
+        let mut codes = Vec::with_capacity(args.len());
+        let mut args = args.iter().map(|arg| arg.trim()).peekable();
+
+        while let Some(arg) = args.next() {
+            let is_category = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphabetic());
+            let code = match args.peek() {
+                Some(next) if is_category && next.starts_with(|c: char| c.is_ascii_digit()) => {
+                    format!("{arg} {}", args.next().unwrap_or_default())
+                }
+                _ => arg.to_string(),
+            };
+            codes.push(code.parse().map_err(Error::InvalidInput)?);
+        }
+
+        Ok(codes)
+    }
+}
+
+impl fmt::Display for TrainId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.category {
+            Some(category) => write!(f, "{category} {}", self.number),
+            None => write!(f, "{}", self.number),
+        }
+    }
+}
+
+/// A train chosen among the ones matching a train code.
+pub struct TrackedTrain {
+    pub code: u32,
+    pub origin_id: String,
+    pub timestamp: i64,
+    pub print_stops: bool,
+}
+
+/// Which run of a train to track, among the ones with the same code.
+#[derive(Debug, Clone, Default)]
+pub struct TrainSelection {
+    /// 0-based index of the candidate to choose
+    pub index: Option<usize>,
+    /// Departure date of the run from its origin
+    pub date: Option<NaiveDate>,
+    /// Name or code of the origin station
+    pub origin: Option<String>,
+}
+
+/// Finds the train with the given code, asking the user to choose one when several trains match.
+/// `hint` tells how the command selects one non-interactively, when it can.
+pub async fn find_train(
+    ctx: &Context,
+    id: &TrainId,
+    selection: &TrainSelection,
+    hint: Option<&str>,
+) -> Result<TrackedTrain> {
+    let code = id.number;
+    let mut lines = ctx.client.autocomplete_train(code).await?;
+
+    if let Some(origin) = &selection.origin {
+        lines.retain(|line| is_from_origin(line, origin));
+        if lines.is_empty() {
+            // Runs from other origins can still be requested directly, e.g. when the number
+            // is reused by another train on a different day
+            let station = station::find_station(ctx, origin).await?;
+            let date = selection.date.unwrap_or_else(time::today);
+            lines.push(train_candidate(code, &station.name, &station.code, date));
+        }
+    }
+
+    if let Some(date) = selection.date {
+        if !lines.iter().any(|line| departure_date(line) == Some(date)) {
+            // The autocomplete only lists the current runs: the runs of other days are requested
+            // directly, from the same origins
+            let mut runs: Vec<TrainCandidate> = Vec::new();
+            for line in &lines {
+                if !runs.iter().any(|run| run.origin_id == line.origin_id) {
+                    runs.push(train_candidate(
+                        code,
+                        origin_name(line),
+                        &line.origin_id,
+                        date,
+                    ));
+                }
+            }
+            lines = runs;
+        }
+        lines.retain(|line| departure_date(line) == Some(date));
+    }
+
+    if let Some(category) = &id.category
+        && lines.len() > 1
+    {
+        lines = with_category(ctx, code, lines, category).await;
+    }
 
     if lines.is_empty() {
-        println!("No train found with the code provided.");
-        return Ok(());
+        return Err(Error::NotFound(format!(
+            "No train found with the code {id}."
+        )));
     }
 
-    let index = if lines.len() > 1 && index.is_none() {
-        println!("Found more than one train with selected code. Please select the desired one:");
+    let descriptions: Vec<_> = lines.iter().map(|l| l.description.clone()).collect();
+    // The --index option is 0-based
+    let pick = selection.index.map(|i| Pick::Index(i + 1));
+    let index = cli_input::select(
+        ctx,
+        &format!("Found more than one train with code {id}."),
+        &descriptions,
+        pick,
+        hint,
+    )?;
+    let line = lines.swap_remove(index);
+
+    Ok(TrackedTrain {
+        code,
+        origin_id: line.origin_id,
+        timestamp: line.timestamp,
+        print_stops: false,
+    })
+}
 
-        lines.clone().into_iter().enumerate().for_each(|(i, l)| {
-            let l = l.split('|').next().unwrap();
-            println!("{}. {}", i + 1, l);
-        });
+/// Keeps the candidates of the given category, which the autocomplete does not return: the
+/// status of each candidate is requested, and its label replaces the number in the description
+/// (e.g. "FR 9612 - NAPOLI CENTRALE - 18/10/26"). Candidates whose status cannot be loaded are
+/// kept.
+async fn with_category(
+    ctx: &Context,
+    code: u32,
+    lines: Vec<TrainCandidate>,
+    category: &str,
+) -> Vec<TrainCandidate> {
+    let statuses = join_all(lines.iter().map(|line| {
+        ctx.client
+            .train_status(&line.origin_id, code, line.timestamp)
+    }))
+    .await;
+
+    lines
+        .into_iter()
+        .zip(statuses)
+        .filter_map(|(mut line, status)| {
+            let Ok(status) = status else {
+                return Some(line);
+            };
+            let label = status.label.as_deref().unwrap_or_default().trim();
+            let matches = status
+                .category
+                .as_deref()
+                .is_some_and(|c| c.trim().eq_ignore_ascii_case(category))
+                || label
+                    .split_whitespace()
+                    .next()
+                    .is_some_and(|c| c.eq_ignore_ascii_case(category));
+            if !matches {
+                return None;
+            }
 
-        cli_input::get_index() - 1
-    } else {
-        index.unwrap_or(0)
-    };
+            if !label.is_empty()
+                && let Some((_, rest)) = line.description.split_once(" - ")
+            {
+                line.description = format!("{label} - {rest}");
+            }
+            Some(line)
+        })
+        .collect()
+}
 
-    if index >= lines.len() {
-        eprintln!("Invalid index.");
-        return Ok(());
+/// Whether the candidate departs from the station with the given code, or whose name contains
+/// the given text.
+fn is_from_origin(line: &TrainCandidate, origin: &str) -> bool {
+    let origin = origin.trim();
+    if station::is_station_code(origin) {
+        return line.origin_id.eq_ignore_ascii_case(origin);
     }
 
-    let mut line_content = lines[index].split('|').nth(1).unwrap().split('-').skip(1);
+    origin_name(line)
+        .to_lowercase()
+        .contains(&origin.to_lowercase())
+}
+
+/// Name of the origin station of a candidate, whose description has the form
+/// "9612 - NAPOLI CENTRALE - 18/10/26".
+fn origin_name(line: &TrainCandidate) -> &str {
+    line.description.split(" - ").nth(1).unwrap_or_default()
+}
+
+fn departure_date(line: &TrainCandidate) -> Option<NaiveDate> {
+    time::from_milliseconds(line.timestamp).map(|t| t.date_naive())
+}
+
+/// A candidate for the run of a train departing on the given date, which the `andamentoTreno`
+/// endpoint identifies by the midnight of that date in the Italian timezone.
+fn train_candidate(code: u32, origin: &str, origin_id: &str, date: NaiveDate) -> TrainCandidate {
+    let midnight = Rome
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .map_or(0, |t| t.timestamp_millis());
+
+    TrainCandidate {
+        description: format!("{code} - {origin} - {}", date.format("%d/%m/%y")),
+        origin_id: origin_id.to_string(),
+        timestamp: midnight,
+    }
+}
+
+/// Options of the watch mode of `track`.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub interval: Duration,
+    /// Which delay changes are reported as events
+    pub diff: DiffOptions,
+    /// Whether to ring the terminal bell when something changes
+    pub bell: bool,
+    /// Command run for each event
+    pub on_event: Option<String>,
+    /// URL receiving each event
+    pub webhook: Option<Webhook>,
+}
+
+/// Number of events shown under the train status in watch mode.
+const SHOWN_EVENTS: usize = 10;
+
+/// A change in the status of a train, found while watching it.
+struct LoggedEvent {
+    time: DateTime<Tz>,
+    train_label: String,
+    event: TrainEvent,
+}
 
-    let origin_id = line_content.next().unwrap();
-    let timestamp = line_content.next().unwrap();
+/// State kept between two refreshes in watch mode, to find what changed in the meantime.
+struct TrackWatch {
+    options: WatchOptions,
+    /// Previous status of each tracked train
+    previous: Vec<Option<TrainStatus>>,
+    events: Vec<LoggedEvent>,
+}
 
-    if auto_refresh {
-        loop {
-            print_train_track_info(origin_id, code, timestamp, print_stops, true).await?;
-            tokio::time::sleep(Duration::from_secs(60)).await;
+impl TrackWatch {
+    fn new(options: WatchOptions, train_count: usize) -> Self {
+        Self {
+            options,
+            previous: vec![None; train_count],
+            events: Vec::new(),
         }
     }
 
-    print_train_track_info(origin_id, code, timestamp, print_stops, false).await?;
+    /// Compares the new status of the train at `index` with the previous one, logging the
+    /// changes. Returns the new events.
+    fn update(&mut self, index: usize, status: &TrainStatus) -> &[LoggedEvent] {
+        let new_events = match &self.previous[index] {
+            Some(previous) => events::diff(previous, status, &self.options.diff),
+            None => Vec::new(),
+        };
+        let logged = self.events.len();
+
+        let time = time::now();
+        let train_label = status.label.as_deref().unwrap_or("--").trim().to_string();
+        self.events
+            .extend(new_events.into_iter().map(|event| LoggedEvent {
+                time,
+                train_label: train_label.clone(),
+                event,
+            }));
+        self.previous[index] = Some(status.clone());
+
+        &self.events[logged..]
+    }
 
-    Ok(())
+    fn print_events(&self) {
+        if self.events.is_empty() {
+            return;
+        }
+
+        // The train is only worth naming when several trains are tracked
+        let show_train = self.previous.len() > 1;
+
+        println!("Events:");
+        let skipped = self.events.len().saturating_sub(SHOWN_EVENTS);
+        for logged in &self.events[skipped..] {
+            let message = if show_train {
+                format!("{}: {}", logged.train_label, logged.event)
+            } else {
+                logged.event.to_string()
+            };
+            let message = match logged.event {
+                TrainEvent::Canceled | TrainEvent::StopSkipped { .. } => message.bright_red(),
+                _ => message.yellow(),
+            };
+            println!("\t{}  {}", format_time(Some(logged.time)), message);
+        }
+        println!();
+    }
 }
 
 async fn print_train_track_info(
-    origin_id: &str,
-    code: u32,
-    timestamp: &str,
-    print_stops: bool,
-    is_watch_mode: bool,
-) -> Result<(), reqwest::Error> {
-    let url = format!(
-        "http://www.viaggiatreno.it/infomobilita/resteasy/viaggiatreno/andamentoTreno/{}/{}/{}",
-        origin_id, code, timestamp
-    );
+    ctx: &Context,
+    trains: &[TrackedTrain],
+    mut watch: Option<&mut TrackWatch>,
+) -> Result<()> {
+    let mut statuses = join_all(
+        trains
+            .iter()
+            .map(|t| ctx.client.train_status(&t.origin_id, t.code, t.timestamp)),
+    )
+    .await;
 
-    let res = reqwest::get(url).await?.json::<serde_json::Value>().await?;
+    // When tracking several trains, the ones whose status cannot be loaded are shown as
+    // unavailable, unless none of them is available
+    if statuses.iter().all(Result::is_err) {
+        return statuses.swap_remove(0).map(|_| ());
+    }
 
-    // Clearing console after new request occurs
-    // With this approach, old tracking data is erased once new data is fetched, avoiding clearing the console and showing blank screen while waiting for new response, with slow connections.
-    if is_watch_mode {
-        //Clear console
-        print!("\x1B[2J\x1B[1;1H");
-        println!(
-            "{}",
-            "Watch mode: refreshing every minute. Press Ctrl+C to exit.".dimmed()
+    let mut new_events = 0;
+    for (index, status) in statuses.iter().enumerate() {
+        let Ok(status) = status else {
+            continue;
+        };
+        history::record_train(ctx, status);
+
+        let Some(watch) = watch.as_mut() else {
+            continue;
+        };
+        let on_event = watch.options.on_event.clone();
+        let webhook = watch.options.webhook.clone();
+        let events = watch.update(index, status);
+        new_events += events.len();
+        for logged in events {
+            let report = TrainEventReport::new(&logged.event, status, logged.time);
+            if let Some(command) = &on_event {
+                hooks::run_event_command(command, &report);
+            }
+            if let Some(webhook) = &webhook {
+                webhook.send(&report);
+            }
+        }
+    }
+
+    if !ctx.output.is_human() {
+        for (train, status) in trains.iter().zip(&statuses) {
+            if let Err(e) = status {
+                eprintln!("Train {} is unavailable: {e}", train.code);
+            }
+        }
+        let statuses: Vec<_> = statuses.into_iter().flatten().collect();
+        return print_machine_readable(ctx.output, &statuses);
+    }
+
+    if let Some(watch) = &watch {
+        watch::clear_screen(watch.options.interval);
+    }
+
+    if let [Ok(status)] = statuses.as_slice() {
+        print_train_status(ctx, status, trains[0].print_stops);
+    } else {
+        print_trains_summary(trains, &statuses);
+        for (train, status) in trains.iter().zip(&statuses) {
+            if let Ok(status) = status
+                && train.print_stops
+            {
+                println!("{}", status.label.as_deref().unwrap_or("--").trim().bold());
+                print_stops_info(ctx, status);
+                println!();
+            }
+        }
+    }
+
+    if let Some(watch) = watch {
+        watch.print_events();
+        if watch.options.bell && new_events > 0 {
+            print!("\x07");
+            // The bell is not followed by a newline, which would flush it
+            io::stdout().flush().ok();
+        }
+    }
+
+    Ok(())
+}
+
+fn print_machine_readable(format: OutputFormat, statuses: &[TrainStatus]) -> Result<()> {
+    let reports: Vec<_> = statuses.iter().map(TrainReport::from).collect();
+
+    match (format, reports.as_slice()) {
+        (OutputFormat::Csv | OutputFormat::Tsv, _) => {
+            let rows: Vec<_> = reports.iter().flat_map(TableRow::from_train).collect();
+            return output::print_rows(format, &rows);
+        }
+        (OutputFormat::Json, [report]) => output::print_document("train_status", report),
+        (OutputFormat::Json, _) => output::print_document("train_statuses", &reports),
+        _ => reports
+            .iter()
+            .for_each(|report| output::print_document("train_status", report)),
+    }
+
+    Ok(())
+}
+
+/// Prints one line for each train, with its itinerary, last detection, delay and next stop.
+fn print_trains_summary(trains: &[TrackedTrain], statuses: &[Result<TrainStatus>]) {
+    let mut table = Table::new("{:<}  {:<}  {:<} {:<}  {:>}  {:<}");
+    table.add_row(row!(
+        "Train".bold(),
+        "Itinerary".bold(),
+        "Last detection".bold(),
+        "",
+        "Delay".bold(),
+        "Next stop".bold()
+    ));
+
+    for (train, status) in trains.iter().zip(statuses) {
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                table.add_row(row!(
+                    train.code.to_string().bold(),
+                    "--",
+                    "--",
+                    "",
+                    "--",
+                    format!("{} ({e})", "Unavailable".bright_red())
+                ));
+                continue;
+            }
+        };
+        let itinerary = format!(
+            "{} - {}",
+            status.origin.as_deref().unwrap_or("--"),
+            status.destination.as_deref().unwrap_or("--")
         );
+        let next_stop = if status.is_canceled() {
+            "Canceled".bright_red().to_string()
+        } else if status.is_not_departed() {
+            format!(
+                "Not yet departed ({})",
+                status.departure_time.as_deref().unwrap_or("--:--")
+            )
+        } else if status.is_arrived() {
+            "Arrived at destination".to_string()
+        } else {
+            status
+                .next_stop()
+                .map(|stop| {
+                    format!(
+                        "{} {}",
+                        stop.station,
+                        format_estimated_time(stop.scheduled_arrival, status.delay.unwrap_or(0))
+                    )
+                })
+                .unwrap_or("--".to_string())
+        };
+
+        table.add_row(row!(
+            status.label.as_deref().unwrap_or("--").trim().bold(),
+            itinerary,
+            status.last_detection_station.as_deref().unwrap_or("--"),
+            format_time(status.last_detection_time),
+            status.delay.map_or("--".to_string(), station::format_delay),
+            next_stop
+        ));
     }
 
-    let international_origin = res["origineEstera"].as_str();
-    let international_destination = res["destinazioneEstera"].as_str();
+    print!("{table}");
+}
+
+fn print_train_status(ctx: &Context, res: &TrainStatus, print_stops: bool) {
+    let international_origin = res.international_origin.as_deref();
+    let international_destination = res.international_destination.as_deref();
 
-    let origin_station = res["origine"].as_str().unwrap_or("--");
-    let destination_station = res["destinazione"].as_str().unwrap_or("--");
+    let origin_station = res.origin.as_deref().unwrap_or("--");
+    let destination_station = res.destination.as_deref().unwrap_or("--");
 
     let mut itinerary = format!("{} - {}", origin_station.cyan(), destination_station.cyan());
     if let Some(international_origin) = international_origin
@@ -105,25 +589,17 @@ async fn print_train_track_info(
         itinerary = format!("{} - {}", itinerary, international_destination.cyan());
     }
 
-    let train_label = res["compNumeroTreno"].as_str().unwrap().trim();
+    let train_label = res.label.as_deref().unwrap_or("--").trim();
 
-    let is_canceled = res["provvedimento"].as_u64().unwrap_or_default() == 1;
+    println!("Train {}, {} ", train_label.bold(), itinerary);
+    print_provisions(res);
 
-    if is_canceled {
-        println!(
-            "Train {}, {} \n{}\n",
-            train_label.bold(),
-            itinerary,
-            "Canceled.".bright_red()
-        );
-        return Ok(());
+    if res.is_canceled() {
+        println!("{}\n", "Canceled.".bright_red());
+        return;
     }
 
-    let is_not_departured = res["nonPartito"].as_bool().unwrap_or_default();
-
-    let stops = res["fermate"].as_array().unwrap();
-
-    let delay_number = res["ritardo"].as_i64();
+    let delay_number = res.delay;
     let delay = delay_number.map(|d| {
         if d > 0 {
             format!("+{d}")
@@ -132,116 +608,114 @@ async fn print_train_track_info(
         }
     });
 
-    if is_not_departured {
+    if res.is_not_departed() {
         let departure_time = if international_origin.is_some() {
-            format_time(&res["oraPartenzaEstera"])
+            format_time(res.international_departure_time)
         } else {
-            res["compOrarioPartenza"]
-                .as_str()
-                .unwrap_or("--:--")
-                .to_string()
+            match res.stops.first().and_then(|s| s.scheduled_departure) {
+                Some(time) => format_time(Some(time)),
+                None => res.departure_time.clone().unwrap_or("--:--".to_string()),
+            }
         };
 
         println!(
-            "Train {}, {} \nNot yet departured.\nScheduled departure time: {}.\n",
-            train_label.bold(),
-            itinerary,
+            "Not yet departured.\nScheduled departure time: {}.\n",
             departure_time
         );
         if print_stops {
-            print_stops_info(stops, delay_number);
+            print_stops_info(ctx, res);
         }
-        return Ok(());
+        return;
     }
 
-    let last_update_station = res["stazioneUltimoRilevamento"].as_str().unwrap_or("--");
-    let last_update_time = format_time(&res["oraUltimoRilevamento"]);
-
-    let is_arrived = !stops.is_empty()
-        && stops.iter().last().unwrap()["actualFermataType"]
-            .as_u64()
-            .unwrap()
-            == 1;
+    let last_update_station = res.last_detection_station.as_deref().unwrap_or("--");
+    let last_update_time = format_time(res.last_detection_time);
 
     println!(
-        "Train {}, {} \nLast update ({}):\n\t{}, {}",
-        train_label.bold(),
-        itinerary,
+        "Last update ({}):\n\t{}, {}",
         last_update_time,
         last_update_station.cyan(),
         delay.unwrap_or("--".to_string()).bold()
     );
 
-    if is_arrived {
+    if res.is_arrived() {
         println!("Arrived at destination.\n");
-    } else {
-        for stop in stops {
-            let stop_type = stop["actualFermataType"].as_u64().unwrap();
-
-            if stop_type != 0 {
-                continue;
-            }
+    } else if let Some(stop) = res.next_stop() {
+        let scheduled_arrival_time = format_time(stop.scheduled_arrival);
+        let estimated_arrival_time =
+            format_estimated_time(stop.scheduled_arrival, delay_number.unwrap_or(0));
 
-            let next_stop = stop["stazione"].as_str().unwrap();
-            let scheduled_arrival_time = format_time(&stop["arrivo_teorico"]);
-            let estimated_arrival_time =
-                format_estimated_time(&stop["arrivo_teorico"], delay_number.unwrap_or(0));
-
-            println!(
-                "\nNext stop: {}\n\tScheduled arrival time: {}\n\tEstimated arrival time: {}\n",
-                next_stop.cyan(),
-                scheduled_arrival_time,
-                estimated_arrival_time,
-            );
-            break;
-        }
+        println!(
+            "\nNext stop: {}\n\tScheduled arrival time: {}\n\tEstimated arrival time: {}\n",
+            stop.station.cyan(),
+            scheduled_arrival_time,
+            estimated_arrival_time,
+        );
     }
 
     if print_stops {
-        print_stops_info(stops, delay_number);
+        print_stops_info(ctx, res);
     }
+}
 
-    Ok(())
+/// Prints the messages about canceled stops, limited runs and reroutes.
+fn print_provisions(status: &TrainStatus) {
+    let provisions = status.provisions();
+    if !provisions.is_empty() {
+        for provision in provisions {
+            println!("{}", provision.yellow());
+        }
+        return;
+    }
+
+    // The API does not always explain why the run differs from the scheduled one
+    let description = match status.run_type {
+        Some(RunType::PartiallyCanceled) => "Some stops are canceled.",
+        Some(RunType::OriginMoved) => "The train departs from a different origin.",
+        Some(RunType::Limited) => "The train ends its run before its destination.",
+        Some(RunType::Rerouted) => "The train is rerouted.",
+        _ => return,
+    };
+    println!("{}", description.yellow());
 }
 
-fn print_stops_info(stops: &[Value], delay: Option<i64>) {
+fn print_stops_info(ctx: &Context, status: &TrainStatus) {
+    let stops = &status.stops;
+    let delay = status.delay;
+    let model = recovery_model(ctx, status);
+    let estimates = model.estimate(status);
+
     print!("Stops:");
 
     for (index, stop) in stops.iter().enumerate() {
-        let stop_type = stop["actualFermataType"].as_u64().unwrap();
-
-        let station = stop["stazione"].as_str().unwrap();
-
-        let scheduled_platform = stop["binarioProgrammatoArrivoDescrizione"]
-            .as_str()
-            .unwrap_or_else(|| {
-                stop["binarioProgrammatoPartenzaDescrizione"]
-                    .as_str()
-                    .unwrap_or("--")
-            });
-
-        let actual_platform = stop["binarioEffettivoArrivoDescrizione"]
-            .as_str()
-            .unwrap_or_else(|| {
-                stop["binarioEffettivoPartenzaDescrizione"]
-                    .as_str()
-                    .unwrap_or("--")
-            });
-
-        let platform = if actual_platform == "--" {
-            scheduled_platform.to_string()
-        } else {
-            actual_platform.green().to_string()
-        };
+        let station = &stop.station;
+
+        let scheduled_platform = stop.scheduled_platform().unwrap_or("--");
 
-        let scheduled_arrival_time = format_time(&stop["arrivo_teorico"]);
-        let scheduled_departure_time = format_time(&stop["partenza_teorica"]);
+        let platform = match stop.actual_platform() {
+            Some(actual_platform) => actual_platform.green().to_string(),
+            None => scheduled_platform.to_string(),
+        };
 
-        if stop_type != 0 {
-            let actual_arrival_time = format_time(&stop["arrivoReale"]);
-            let actual_departure_time = format_time(&stop["partenzaReale"]);
+        let scheduled_arrival_time = format_time(stop.scheduled_arrival);
+        let scheduled_departure_time = format_time(stop.scheduled_departure);
 
-            println!("\n{} - platform {}", station.green(), platform);
+        if stop.kind == StopKind::Canceled {
+            println!(
+                "\n{} - {}",
+                station.strikethrough().bright_red(),
+                "canceled".bright_red()
+            );
+        } else if stop.kind != StopKind::Pending {
+            let actual_arrival_time = format_time(stop.actual_arrival);
+            let actual_departure_time = format_time(stop.actual_departure);
+
+            let added = if stop.kind == StopKind::Extraordinary {
+                format!(" {}", "(added stop)".yellow())
+            } else {
+                String::new()
+            };
+            println!("\n{} - platform {}{}", station.green(), platform, added);
             if index != 0 {
                 println!(
                     "\tScheduled arrival time:   {} - actual: {}",
@@ -258,61 +732,76 @@ fn print_stops_info(stops: &[Value], delay: Option<i64>) {
             }
         } else {
             let estimated_arrival_time =
-                format_estimated_time(&stop["arrivo_teorico"], delay.unwrap_or(0));
+                format_estimated_time(stop.scheduled_arrival, delay.unwrap_or(0));
             let estimated_departure_time =
-                format_estimated_time(&stop["partenza_teorica"], delay.unwrap_or(0));
+                format_estimated_time(stop.scheduled_departure, delay.unwrap_or(0));
+
+            let estimate = estimates[index];
+            let refined_arrival_time = format_time(estimate.and_then(|e| e.arrival));
+            let refined_departure_time = format_time(estimate.and_then(|e| e.departure));
 
             println!("\n{} - platform {}", station, platform);
             if index != 0 {
                 println!(
-                    "\tScheduled arrival time:   {} - estimated: {}",
+                    "\tScheduled arrival time:   {} - estimated: {} (with recovery: {})",
                     scheduled_arrival_time,
                     estimated_arrival_time.bold(),
+                    refined_arrival_time.bold(),
                 );
             }
             if index != stops.len() - 1 {
                 println!(
-                    "\tScheduled departure time: {} - estimated: {}",
+                    "\tScheduled departure time: {} - estimated: {} (with recovery: {})",
                     scheduled_departure_time,
-                    estimated_departure_time.bold()
+                    estimated_departure_time.bold(),
+                    refined_departure_time.bold()
                 );
             }
         }
     }
 
+    if estimates.iter().any(Option::is_some) {
+        let note = if model.segment_recovery.is_empty() {
+            format!(
+                "Times with recovery are estimated: the delay is assumed to shrink by {}% of the \
+                 running time between stops and by the dwell time beyond {} min.",
+                model.recovery_rate * 100.0,
+                model.min_dwell
+            )
+        } else {
+            "Times with recovery are estimated from the delay usually recovered between stops, \
+             as recorded in the history, or from the timetable margins where it is not known."
+                .to_string()
+        };
+        println!("\n{}", note.dimmed());
+    }
+
     println!();
 }
 
-fn format_time(time: &Value) -> String {
-    parse_time(time.as_u64())
-        .map(|t| t.format("%H:%M").to_string())
-        .unwrap_or("--:--".to_string())
-}
+/// Recovery model of a train, learning the recovery of each segment from the delay history when
+/// it has been recorded on enough days.
+fn recovery_model(ctx: &Context, status: &TrainStatus) -> RecoveryModel {
+    let mut model = RecoveryModel::default();
 
-fn format_estimated_time(time: &Value, delay: i64) -> String {
-    const MICROSECONDS_PER_MINUTE: i64 = 60_000;
+    if let (Some(store), Some(number)) = (&ctx.history, status.number)
+        && let Ok(observations) = store.load(number)
+    {
+        for (from, to, recovery) in history::segment_recovery(&observations, &status.stops) {
+            model.segment_recovery.insert((from, to), recovery);
+        }
+    }
 
-    parse_time(
-        time.as_i64()
-            .map(|t| (t + MICROSECONDS_PER_MINUTE * delay) as u64),
-    )
-    .map(|t| t.format("%H:%M").to_string())
-    .unwrap_or("--:--".to_string())
+    model
 }
 
-fn parse_time(time: Option<u64>) -> Option<NaiveTime> {
-    const SECONDS_PER_DAY: u32 = 86400;
-
-    let italian_timezone_offset = Rome
-        .offset_from_utc_datetime(&Local::now().naive_utc())
-        .fix()
-        .local_minus_utc() as u32;
+/// Formats a time as "HH:MM", with the date when it is not today.
+pub fn format_time(time: Option<DateTime<Tz>>) -> String {
+    time.map(|t| time::format_time(t, time::today()))
+        .unwrap_or("--:--".to_string())
+}
 
-    time.map(|t| {
-        NaiveTime::from_num_seconds_from_midnight_opt(
-            ((t / 1000) as u32 + italian_timezone_offset) % SECONDS_PER_DAY,
-            0,
-        )
-        .unwrap()
-    })
+/// Formats a scheduled time shifted by the delay in minutes.
+pub fn format_estimated_time(time: Option<DateTime<Tz>>, delay: i64) -> String {
+    format_time(time.map(|t| t + TimeDelta::minutes(delay)))
 }
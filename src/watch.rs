@@ -0,0 +1,89 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::Duration;
+
+use colored::Colorize;
+use crossterm::{cursor, execute};
+use viaggiatreno::Result;
+
+/// Default time between two refreshes in watch mode.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Calls `refresh` every `interval` until the user presses Ctrl+C, which ends the watch
+/// successfully.
+/// Only an error of the first refresh ends the watch: later ones, such as a transient network
+/// error, are printed under the last screen and the refresh is tried again on the next tick.
+pub async fn run(interval: Duration, mut refresh: impl AsyncFnMut() -> Result<()>) -> Result<()> {
+    let _terminal = TerminalGuard::new();
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    let mut refreshed = false;
+    loop {
+        tokio::select! {
+            result = refresh() => match result {
+                Ok(()) => refreshed = true,
+                Err(e) if refreshed => {
+                    eprintln!("{}", format!("Refresh failed, retrying at the next one: {e}").red())
+                }
+                Err(e) => return Err(e),
+            },
+            _ = &mut ctrl_c => return Ok(()),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = &mut ctrl_c => return Ok(()),
+        }
+    }
+}
+
+/// Clears the console and prints the watch mode header.
+/// It is meant to be called once the new data has been fetched, so that old data is not erased
+/// while waiting for a slow response.
+pub fn clear_screen(interval: Duration) {
+    print!("\x1B[2J\x1B[1;1H");
+    println!(
+        "{}",
+        format!(
+            "Watch mode: refreshing {}. Press Ctrl+C to exit.",
+            describe_interval(interval)
+        )
+        .dimmed()
+    );
+}
+
+fn describe_interval(interval: Duration) -> String {
+    match interval.as_secs() {
+        60 => "every minute".to_string(),
+        1 => "every second".to_string(),
+        s if s.is_multiple_of(60) => format!("every {} minutes", s / 60),
+        s => format!("every {} seconds", s),
+    }
+}
+
+/// Hides the cursor while watching, and restores the terminal when dropped.
+struct TerminalGuard {
+    is_terminal: bool,
+}
+
+impl TerminalGuard {
+    fn new() -> Self {
+        let is_terminal = io::stdout().is_terminal();
+        if is_terminal {
+            let _ = execute!(io::stdout(), cursor::Hide);
+        }
+        Self { is_terminal }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.is_terminal {
+            // Resetting colors, in case the watch was interrupted while printing
+            print!("\x1B[0m");
+            let _ = execute!(io::stdout(), cursor::Show);
+            println!();
+            let _ = io::stdout().flush();
+        }
+    }
+}
@@ -0,0 +1,1582 @@
+mod common;
+
+use chrono::TimeZone;
+use chrono_tz::Europe::Rome;
+use common::{
+    StubServer, WebhookListener, json, json_sequence, run_cli, run_cli_in, spawn_cli_in,
+    temp_data_dir, text,
+};
+
+const TRAIN_STATUS: &str = include_str!("fixtures/andamento_treno_9612.json");
+const INTERCITY_STATUS: &str = include_str!("fixtures/andamento_treno_8805.json");
+const TRAIN_AUTOCOMPLETE: &str = include_str!("fixtures/autocomplete_treno_9612.txt");
+const INTERCITY_AUTOCOMPLETE: &str = include_str!("fixtures/autocomplete_treno_8805.txt");
+const LIMITED_TRAIN_STATUS: &str = include_str!("fixtures/andamento_treno_2143.json");
+const LIMITED_TRAIN_AUTOCOMPLETE: &str = include_str!("fixtures/autocomplete_treno_2143.txt");
+const STATION_AUTOCOMPLETE: &str = include_str!("fixtures/autocomplete_stazione_bologna.txt");
+const AMBIGUOUS_STATION_AUTOCOMPLETE: &str = include_str!("fixtures/autocomplete_stazione_bol.txt");
+const FIRENZE_AUTOCOMPLETE: &str = include_str!("fixtures/autocomplete_stazione_firenze.txt");
+const ROMA_AUTOCOMPLETE: &str = include_str!("fixtures/autocomplete_stazione_roma.txt");
+const TRAVEL_SOLUTIONS: &str = include_str!("fixtures/soluzioni_viaggio_5043_6421.json");
+const ARRIVALS: &str = include_str!("fixtures/arrivi_S05043.json");
+const DEPARTURES: &str = include_str!("fixtures/partenze_S05043.json");
+const NEWS: &str = include_str!("fixtures/infomobilita_rss.html");
+
+fn viaggiatreno_stub() -> StubServer {
+    viaggiatreno_stub_with_boards(ARRIVALS, DEPARTURES)
+}
+
+/// Stub serving the given station boards, for tests that depend on the time of the trains.
+fn viaggiatreno_stub_with_boards(arrivals: &str, departures: &str) -> StubServer {
+    StubServer::start(vec![
+        text("cercaNumeroTrenoTrenoAutocomplete/9612", TRAIN_AUTOCOMPLETE),
+        text(
+            "cercaNumeroTrenoTrenoAutocomplete/8805",
+            INTERCITY_AUTOCOMPLETE,
+        ),
+        text(
+            "cercaNumeroTrenoTrenoAutocomplete/2143",
+            LIMITED_TRAIN_AUTOCOMPLETE,
+        ),
+        text("cercaNumeroTrenoTrenoAutocomplete/", ""),
+        json("andamentoTreno/S09218/9612/1792274400000", TRAIN_STATUS),
+        json(
+            "andamentoTreno/S05043/2143/1792274400000",
+            LIMITED_TRAIN_STATUS,
+        ),
+        json("andamentoTreno/S01700/8805/1792274400000", INTERCITY_STATUS),
+        text("autocompletaStazione/Bologna", STATION_AUTOCOMPLETE),
+        text("autocompletaStazione/Bol", AMBIGUOUS_STATION_AUTOCOMPLETE),
+        text("autocompletaStazione/Firenze", FIRENZE_AUTOCOMPLETE),
+        text("autocompletaStazione/Roma", ROMA_AUTOCOMPLETE),
+        text("autocompletaStazione/", ""),
+        json("soluzioniViaggioNew/5043/6421/", TRAVEL_SOLUTIONS),
+        json("arrivi/", arrivals),
+        json("partenze/", departures),
+        text("infomobilitaRSS/false", NEWS),
+    ])
+}
+
+/// Station board with the scheduled time of some trains moved to the given minutes from now.
+fn board_relative_to_now(board: &str, key: &str, minutes: &[(u64, i64)]) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64;
+    let mut entries: Vec<serde_json::Value> = serde_json::from_str(board).unwrap();
+
+    for entry in &mut entries {
+        let number = entry["numeroTreno"].as_u64();
+        if let Some((_, offset)) = minutes.iter().find(|(n, _)| Some(*n) == number) {
+            entry[key] = serde_json::json!(now + offset * 60_000);
+        }
+    }
+
+    serde_json::to_string(&entries).unwrap()
+}
+
+/// Station boards where FR 9612 passed an hour ago.
+fn boards_with_passed_train() -> (String, String) {
+    (
+        board_relative_to_now(ARRIVALS, "orarioArrivo", &[(9612, -60)]),
+        board_relative_to_now(DEPARTURES, "orarioPartenza", &[(9612, -60)]),
+    )
+}
+
+fn stdout(output: &std::process::Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn track_prints_train_status() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "9612"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Train FR 9612, NAPOLI CENTRALE - MILANO CENTRALE"));
+    assert!(stdout.contains("ROMA TERMINI, +3"));
+    assert!(stdout.contains("Next stop: FIRENZE S. M. NOVELLA"));
+}
+
+#[test]
+fn track_prints_the_date_of_a_departure_on_another_day() {
+    let departure = Rome.with_ymd_and_hms(2020, 1, 2, 0, 10, 0).unwrap();
+    let mut status: serde_json::Value = serde_json::from_str(TRAIN_STATUS).unwrap();
+    status["nonPartito"] = serde_json::json!(true);
+    status["fermate"][0]["partenza_teorica"] = serde_json::json!(departure.timestamp_millis());
+    let server = StubServer::start(vec![
+        text("cercaNumeroTrenoTrenoAutocomplete/9612", TRAIN_AUTOCOMPLETE),
+        json("andamentoTreno/S09218/9612/", &status.to_string()),
+    ]);
+    let output = run_cli(&server, &["track", "9612", "--no-history"]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Scheduled departure time: 02/01 00:10."));
+}
+
+#[test]
+fn track_prints_stops() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "9612", "--stops"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Stops:"));
+    assert!(stdout.contains("ROMA TERMINI - platform 10"));
+    assert!(stdout.contains("BOLOGNA CENTRALE - platform 16"));
+    assert!(stdout.contains("MILANO CENTRALE - platform 21"));
+}
+
+#[test]
+fn track_short_stops_flag_before_code() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "-s", "9612"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Stops:"));
+    assert!(stdout.contains("ROMA TERMINI - platform 10"));
+}
+
+#[test]
+fn track_stops_show_estimate_with_recovery() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "8805", "--stops", "--no-history"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    // Bologna is scheduled at 10:27 with 12 minutes of delay
+    assert!(stdout.contains("10:39 (with recovery: "));
+    assert!(stdout.contains("10:36)"));
+    assert!(stdout.contains("Times with recovery are estimated: "));
+}
+
+#[test]
+fn track_stops_learn_recovery_from_history() {
+    let server = viaggiatreno_stub();
+    let data_dir = temp_data_dir();
+    std::fs::create_dir_all(&data_dir).unwrap();
+
+    // On three past days the train recovered 12 minutes between Milano and Bologna
+    let history: String = ["2026-10-15", "2026-10-16", "2026-10-17"]
+        .iter()
+        .flat_map(|date| {
+            [
+                ("S01700", "MILANO CENTRALE", None, Some(12)),
+                ("S05043", "BOLOGNA CENTRALE", Some(0), None),
+            ]
+            .map(|(code, station, arrival_delay, departure_delay)| {
+                serde_json::json!({
+                    "train_number": 8805,
+                    "train_label": "IC 8805",
+                    "date": date,
+                    "station_code": code,
+                    "station": station,
+                    "arrival_delay": arrival_delay,
+                    "departure_delay": departure_delay,
+                    "recorded_at": format!("{date}T20:00:00Z"),
+                })
+                .to_string()
+                    + "\n"
+            })
+        })
+        .collect();
+    std::fs::write(data_dir.join("history.jsonl"), history).unwrap();
+
+    let output = run_cli_in(&server, &data_dir, &["track", "8805", "--stops"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("10:39 (with recovery: "));
+    assert!(stdout.contains("10:27)"));
+    assert!(stdout.contains("as recorded in the history"));
+}
+
+#[test]
+fn track_unknown_train_exits_with_not_found() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "1"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No train found"));
+}
+
+#[test]
+fn station_prints_arrivals_and_departures() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "Bologna"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Arrivals"));
+    assert!(stdout.contains("Departures"));
+    assert!(stdout.contains("PORRETTA TERME"));
+    assert!(stdout.contains("10:33"));
+    assert!(stdout.contains("+12"));
+}
+
+#[test]
+fn station_prints_the_date_of_trains_on_another_day() {
+    let departure = Rome.with_ymd_and_hms(2020, 1, 2, 0, 10, 0).unwrap();
+    let mut departures: serde_json::Value = serde_json::from_str(DEPARTURES).unwrap();
+    departures[0]["orarioPartenza"] = serde_json::json!(departure.timestamp_millis());
+    let server = viaggiatreno_stub_with_boards(ARRIVALS, &departures.to_string());
+    let output = run_cli(&server, &["station", "Bologna", "--departures"]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("02/01 00:10"));
+}
+
+#[test]
+fn station_filters_by_train_type() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "S05043", "--departures", "-f", "IC"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(!stdout.contains("Arrivals"));
+    assert!(stdout.contains("IC 8805"));
+    assert!(!stdout.contains("FR 9612"));
+}
+
+#[test]
+fn news_prints_expanded_items() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["news", "--verbose"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("1. Linea Firenze - Roma: circolazione rallentata"));
+    assert!(stdout.contains("2. Sciopero del personale del 24 ottobre"));
+    assert!(stdout.contains("guasto alla linea"));
+}
+
+#[test]
+fn base_url_flag_overrides_environment() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &["--base-url", "http://127.0.0.1:1", "news", "--verbose"],
+    );
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(server.requests().is_empty());
+}
+
+fn json_lines(output: &std::process::Output) -> Vec<serde_json::Value> {
+    stdout(output)
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+#[test]
+fn track_json_output() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "9612", "--output", "json"]);
+    let documents = json_lines(&output);
+
+    assert!(output.status.success());
+    assert_eq!(documents.len(), 1);
+
+    let document = &documents[0];
+    assert_eq!(document["schema_version"], 1);
+    assert_eq!(document["kind"], "train_status");
+
+    let train = &document["data"];
+    assert_eq!(train["label"], "FR 9612");
+    assert_eq!(train["delay"], 3);
+    assert_eq!(train["departed"], true);
+    assert_eq!(train["next_stop"], "FIRENZE S. M. NOVELLA");
+    assert_eq!(train["stops"].as_array().unwrap().len(), 5);
+    assert_eq!(train["stops"][1]["kind"], "regular");
+    assert_eq!(train["stops"][1]["actual_platform"], "10");
+    assert_eq!(
+        train["stops"][2]["scheduled_arrival"],
+        "2026-10-18T09:32:00+02:00"
+    );
+    assert_eq!(
+        train["stops"][2]["estimated_arrival"],
+        "2026-10-18T09:35:00+02:00"
+    );
+}
+
+#[test]
+fn station_ndjson_output() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "S05043", "--output", "ndjson"]);
+    let documents = json_lines(&output);
+
+    assert!(output.status.success());
+    assert_eq!(documents.len(), 6);
+    assert!(documents[..3].iter().all(|d| d["kind"] == "arrival"));
+    assert!(documents[3..].iter().all(|d| d["kind"] == "departure"));
+    assert_eq!(documents[0]["data"]["station"], "NAPOLI CENTRALE");
+    assert_eq!(documents[5]["data"]["label"], "IC 8805");
+    assert_eq!(documents[5]["data"]["delay"], 12);
+}
+
+#[test]
+fn station_json_output_only_includes_requested_boards() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "S05043", "--arrivals", "-o", "json"]);
+    let documents = json_lines(&output);
+
+    assert_eq!(documents[0]["kind"], "station_board");
+    assert_eq!(documents[0]["data"]["station_code"], "S05043");
+    assert_eq!(
+        documents[0]["data"]["arrivals"].as_array().unwrap().len(),
+        3
+    );
+    assert!(documents[0]["data"].get("departures").is_none());
+}
+
+#[test]
+fn news_json_output() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["news", "-o", "json"]);
+    let documents = json_lines(&output);
+
+    assert!(output.status.success());
+    assert_eq!(documents[0]["kind"], "news");
+
+    let news = documents[0]["data"].as_array().unwrap();
+    assert_eq!(news.len(), 2);
+    assert_eq!(news[0]["highlighted"], true);
+    assert_eq!(news[1]["highlighted"], false);
+    assert!(news[1]["body"].as_str().unwrap().contains("servizi minimi"));
+}
+
+#[test]
+fn track_csv_output() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "9612", "--output", "csv"]);
+    let stdout = stdout(&output);
+    let lines: Vec<_> = stdout.lines().collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines[0],
+        "train,station,scheduled_time,delay,scheduled_platform,actual_platform,kind"
+    );
+    assert_eq!(lines.len(), 6);
+    assert_eq!(
+        lines[2],
+        "FR 9612,ROMA TERMINI,2026-10-18 07:50,3,8,10,regular"
+    );
+    assert_eq!(
+        lines[3],
+        "FR 9612,FIRENZE S. M. NOVELLA,2026-10-18 09:32,3,9,,pending"
+    );
+}
+
+#[test]
+fn station_tsv_output() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "S05043", "--output", "tsv"]);
+    let stdout = stdout(&output);
+    let lines: Vec<_> = stdout.lines().collect();
+
+    assert!(output.status.success());
+    assert_eq!(lines.len(), 7);
+    assert_eq!(
+        lines[1],
+        "FR 9612\tNAPOLI CENTRALE\t2026-10-18 10:17\t3\t16\t16\tarrival"
+    );
+    assert_eq!(
+        lines[6],
+        "IC 8805\tLECCE\t2026-10-18 10:33\t12\t6\t\tdeparture"
+    );
+}
+
+#[test]
+fn ambiguous_station_fails_without_prompting() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "Bol", "--non-interactive"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr.contains("2. BOLOGNA CENTRALE (S05043)"));
+    assert!(stderr.contains("--pick"));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn ambiguous_station_of_other_commands_suggests_station_code() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["plan", "Bol", "Firenze", "--non-interactive"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr.contains("Use the station code"));
+    assert!(!stderr.contains("--pick"));
+}
+
+#[test]
+fn station_pick_selects_candidate() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "Bol", "--pick", "2"]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("PORRETTA TERME"));
+    assert!(
+        server
+            .requests()
+            .iter()
+            .any(|r| r.contains("partenze/S05043/"))
+    );
+}
+
+#[test]
+fn station_pick_first() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &["station", "Bol", "--pick", "first", "-o", "json"],
+    );
+    let documents = json_lines(&output);
+
+    assert_eq!(documents[0]["data"]["station_code"], "S05038");
+}
+
+#[test]
+fn station_pick_out_of_range_is_invalid_input() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["station", "Bol", "--pick", "4"]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn news_does_not_prompt_without_terminal() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["news"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("1. Linea Firenze - Roma"));
+    assert!(!stdout.contains("guasto alla linea"));
+}
+
+#[test]
+fn plan_prints_travel_solutions() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &["plan", "Bologna", "Firenze", "--at", "2026-10-18 18:00"],
+    );
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("1. 18:05 - 18:42  (00:37, direct)"));
+    assert!(stdout.contains("2. 18:12 - 20:04  (01:52, 1 change)"));
+    assert!(stdout.contains("Regionale 18921  PRATO CENTRALE 19:44 - FIRENZE S. M. NOVELLA 20:04"));
+    assert!(stdout.contains("infotreno-cli track"));
+    assert!(
+        server
+            .requests()
+            .iter()
+            .any(|r| r.ends_with("soluzioniViaggioNew/5043/6421/2026-10-18T18:00:00"))
+    );
+}
+
+#[test]
+fn plan_prints_the_date_of_solutions_on_another_day() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &["plan", "Bologna", "Firenze", "--at", "2026-10-17 23:30"],
+    );
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("1. 18/10 18:05 - 18/10 18:42  (00:37, direct)"));
+}
+
+#[test]
+fn plan_json_output() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["plan", "S05043", "S06421", "-o", "json"]);
+    let documents = json_lines(&output);
+
+    assert!(output.status.success());
+    assert_eq!(documents[0]["kind"], "travel_solutions");
+
+    let solutions = documents[0]["data"].as_array().unwrap();
+    assert_eq!(solutions.len(), 2);
+    assert_eq!(solutions[1]["changes"], 1);
+    assert_eq!(solutions[1]["departure"], "2026-10-18T18:12:00+02:00");
+    assert_eq!(solutions[1]["legs"][1]["train_number"], "18921");
+}
+
+#[test]
+fn history_reports_recorded_delays() {
+    let (arrivals, departures) = boards_with_passed_train();
+    let server = viaggiatreno_stub_with_boards(&arrivals, &departures);
+    let data_dir = temp_data_dir();
+
+    assert!(
+        run_cli_in(&server, &data_dir, &["track", "9612"])
+            .status
+            .success()
+    );
+    assert!(
+        run_cli_in(&server, &data_dir, &["station", "Bologna"])
+            .status
+            .success()
+    );
+
+    let output = run_cli_in(
+        &server,
+        &data_dir,
+        &["history", "9612", "--station", "roma"],
+    );
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Delay history of train FR 9612 at ROMA TERMINI"));
+    assert!(stdout.contains("Sun 18/10/2026"));
+    assert!(stdout.contains("Days: 1  Mean delay: 4.0 min"));
+    assert!(stdout.contains("On time: 100%"));
+
+    let output = run_cli_in(
+        &server,
+        &data_dir,
+        &["history", "9612", "--station", "S05043", "-o", "json"],
+    );
+    let documents = json_lines(&output);
+
+    assert_eq!(documents[0]["kind"], "delay_history");
+    assert_eq!(documents[0]["data"]["station"], "BOLOGNA CENTRALE");
+    assert_eq!(documents[0]["data"]["days"][0]["arrival_delay"], 3);
+    assert_eq!(documents[0]["data"]["stats"]["p90"], 3);
+}
+
+#[test]
+fn station_records_only_trains_already_passed() {
+    let arrivals = board_relative_to_now(ARRIVALS, "orarioArrivo", &[(9612, -60), (2143, 60)]);
+    let server = viaggiatreno_stub_with_boards(&arrivals, "[]");
+    let data_dir = temp_data_dir();
+
+    assert!(
+        run_cli_in(&server, &data_dir, &["station", "Bologna"])
+            .status
+            .success()
+    );
+
+    let history = std::fs::read_to_string(data_dir.join("history.jsonl")).unwrap();
+    let observations: Vec<serde_json::Value> = history
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert!(
+        observations
+            .iter()
+            .any(|o| o["train_number"] == 9612 && o["estimated"] == true)
+    );
+    assert!(!observations.iter().any(|o| o["train_number"] == 2143));
+}
+
+#[test]
+fn board_estimates_do_not_replace_tracked_delays() {
+    let (arrivals, departures) = boards_with_passed_train();
+    let server = viaggiatreno_stub_with_boards(&arrivals, &departures);
+    let data_dir = temp_data_dir();
+
+    // Tracked 4 minutes late in Roma, then shown with 3 minutes of delay on the board
+    for args in [["track", "9612"], ["station", "Roma"]] {
+        assert!(run_cli_in(&server, &data_dir, &args).status.success());
+    }
+
+    let output = run_cli_in(
+        &server,
+        &data_dir,
+        &["history", "9612", "--station", "roma"],
+    );
+
+    assert!(stdout(&output).contains("Days: 1  Mean delay: 4.0 min"));
+}
+
+#[test]
+fn history_without_records_is_not_found() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["history", "9612", "--station", "Roma"]);
+
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn no_history_flag_disables_recording() {
+    let server = viaggiatreno_stub();
+    let data_dir = temp_data_dir();
+
+    assert!(
+        run_cli_in(&server, &data_dir, &["track", "9612", "--no-history"])
+            .status
+            .success()
+    );
+    assert!(!data_dir.exists());
+}
+
+#[test]
+fn line_lists_trains_between_stations() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["line", "Roma", "Firenze"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("FR 9612"));
+    assert!(stdout.contains("ROMA TERMINI → FIRENZE S. M. NOVELLA"));
+    assert!(stdout.contains("+3"));
+    assert!(!stdout.contains("REG 2143"));
+}
+
+#[test]
+fn line_finds_trains_on_intermediate_boards() {
+    // IC 8805 has left Milano, IC 8807 has already passed Bologna and is on neither end's boards
+    let departures: Vec<serde_json::Value> = serde_json::from_str(DEPARTURES).unwrap();
+    let board_entry = departures
+        .into_iter()
+        .find(|e| e["numeroTreno"] == 8805)
+        .unwrap();
+    let mut other_entry = board_entry.clone();
+    other_entry["numeroTreno"] = 8807.into();
+    other_entry["compNumeroTreno"] = "IC 8807".into();
+
+    let mut other_status: serde_json::Value = serde_json::from_str(INTERCITY_STATUS).unwrap();
+    other_status["numeroTreno"] = 8807.into();
+    other_status["compNumeroTreno"] = "IC 8807".into();
+    other_status["fermate"][1]["arrivoReale"] = 1792312740000_i64.into();
+    other_status["fermate"][1]["partenzaReale"] = 1792313100000_i64.into();
+    other_status["fermate"][1]["actualFermataType"] = 1.into();
+
+    let server = StubServer::start(vec![
+        json(
+            "partenze/S01700",
+            &serde_json::json!([board_entry]).to_string(),
+        ),
+        json(
+            "partenze/S05043",
+            &serde_json::json!([other_entry]).to_string(),
+        ),
+        json("arrivi/", "[]"),
+        json("partenze/", "[]"),
+        json("andamentoTreno/S01700/8805/", INTERCITY_STATUS),
+        json("andamentoTreno/S01700/8807/", &other_status.to_string()),
+    ]);
+    let output = run_cli(&server, &["line", "S01700", "S11145"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("IC 8805"));
+    assert!(stdout.contains("IC 8807"));
+    assert!(
+        server
+            .requests()
+            .iter()
+            .any(|r| r.contains("partenze/S05043"))
+    );
+}
+
+#[test]
+fn line_skips_trains_not_between_stations() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["--output", "json", "line", "Firenze", "Bologna"]);
+    let document: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(document["kind"], "line_trains");
+    assert_eq!(document["data"], serde_json::json!([]));
+}
+
+#[test]
+fn csv_output_without_rows_has_header() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["--output", "csv", "line", "Firenze", "Bologna"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "number,label,category,origin,destination,direction,last_detection_station,\
+         last_detection_time,delay,next_stop\n"
+    );
+}
+
+#[test]
+fn long_distance_lists_running_trains() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["long-distance"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("FR 9612"));
+    assert!(stdout.contains("IC 8805"));
+    assert!(stdout.contains("MODENA"));
+    assert!(!stdout.contains("REG"));
+}
+
+#[test]
+fn long_distance_sorts_by_delay() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &["--output", "ndjson", "long-distance", "--sort", "delay"],
+    );
+    let lines = json_lines(&output);
+
+    assert!(output.status.success());
+    let labels: Vec<_> = lines.iter().map(|l| l["data"]["label"].clone()).collect();
+    assert_eq!(labels, ["IC 8805", "FR 9612"]);
+    assert_eq!(lines[0]["kind"], "running_train");
+    assert_eq!(lines[0]["data"]["delay"], 12);
+}
+
+#[test]
+fn long_distance_polls_every_hub_station() {
+    // Codes of the hubs, checked against the names of the stops of the recorded trains
+    let hubs = [
+        ("S01700", "MILANO CENTRALE"),
+        ("S00219", "TORINO PORTA NUOVA"),
+        ("S02593", "VENEZIA SANTA LUCIA"),
+        ("S05043", "BOLOGNA CENTRALE"),
+        ("S06421", "FIRENZE S. M. NOVELLA"),
+        ("S08409", "ROMA TERMINI"),
+        ("S09218", "NAPOLI CENTRALE"),
+        ("S11119", "BARI CENTRALE"),
+        ("S11781", "REGGIO DI CALABRIA CENTRALE"),
+    ];
+    let recorded_stops: Vec<(String, String)> = [
+        TRAIN_STATUS,
+        INTERCITY_STATUS,
+        include_str!("fixtures/andamento_treno_1963.json"),
+    ]
+    .iter()
+    .flat_map(|status| {
+        let status: serde_json::Value = serde_json::from_str(status).unwrap();
+        status["fermate"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|stop| {
+                (
+                    stop["id"].as_str().unwrap().to_string(),
+                    stop["stazione"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect::<Vec<_>>()
+    })
+    .collect();
+    for (code, name) in recorded_stops {
+        if let Some((_, hub)) = hubs.iter().find(|(hub_code, _)| *hub_code == code) {
+            assert_eq!(*hub, name, "{code}");
+        }
+    }
+
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["long-distance"]);
+
+    assert!(output.status.success());
+    let requests = server.requests();
+    for (code, name) in hubs {
+        assert!(
+            requests
+                .iter()
+                .any(|r| r.contains(&format!("partenze/{code}/"))),
+            "{name} not polled"
+        );
+    }
+}
+
+#[test]
+fn line_plot_draws_stations_and_trains() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["line", "Roma", "Bologna", "--plot", "--ascii"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains(" +  ROMA TERMINI"));
+    assert!(stdout.contains(" +  FIRENZE S. M. NOVELLA"));
+    assert!(stdout.contains(" +  BOLOGNA CENTRALE"));
+    assert!(stdout.contains("v FR 9612 +3"));
+}
+
+#[test]
+fn branch_lists_bundled_and_user_branches() {
+    let server = viaggiatreno_stub();
+    let data_dir = temp_data_dir();
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(
+        data_dir.join("branches.json"),
+        r#"[{"name": "direttissima", "stations": [{"name": "ROMA TERMINI", "code": "S08409"}, {"name": "FIRENZE S. M. NOVELLA", "code": "S06421"}]}]"#,
+    )
+    .unwrap();
+    let output = run_cli_in(&server, &data_dir, &["branch", "--list"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("porrettana"));
+    assert!(stdout.contains("direttissima"));
+}
+
+#[test]
+fn branch_list_as_csv_has_one_row_per_branch() {
+    let server = viaggiatreno_stub();
+    let data_dir = temp_data_dir();
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(
+        data_dir.join("branches.json"),
+        r#"[{"name": "direttissima", "stations": [{"name": "ROMA TERMINI", "code": "S08409"}, {"name": "FIRENZE S. M. NOVELLA", "code": "S06421"}]}]"#,
+    )
+    .unwrap();
+    let output = run_cli_in(&server, &data_dir, &["--output", "csv", "branch", "--list"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with("name,description,stations\n"));
+    assert!(stdout.contains("\ndirettissima,,ROMA TERMINI;FIRENZE S. M. NOVELLA\n"));
+    assert!(stdout.contains("\nporrettana,"));
+}
+
+#[test]
+fn branch_shows_next_stop_and_estimated_arrival() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &[
+            "--output",
+            "json",
+            "branch",
+            "--stations",
+            "Roma,Firenze,Bologna",
+            "--filter",
+            "FR",
+        ],
+    );
+    let document: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(document["kind"], "branch_trains");
+    let train = &document["data"][0];
+    assert_eq!(train["label"], "FR 9612");
+    assert_eq!(train["next_stop"], "FIRENZE S. M. NOVELLA");
+    assert_eq!(
+        train["next_stop_estimated_arrival"],
+        "2026-10-18T09:35:00+02:00"
+    );
+}
+
+#[test]
+fn bundled_branches_do_not_look_stations_up() {
+    let server = StubServer::start(vec![json("arrivi/", "[]"), json("partenze/", "[]")]);
+
+    for name in ["porrettana", "faentina"] {
+        assert!(run_cli(&server, &["branch", name]).status.success());
+    }
+    assert!(
+        !server
+            .requests()
+            .iter()
+            .any(|r| r.contains("autocompletaStazione"))
+    );
+}
+
+#[test]
+fn branch_unknown_name_is_not_found() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["branch", "unknown"]);
+
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[cfg(unix)]
+#[test]
+fn station_watch_refreshes_until_interrupted() {
+    let server = viaggiatreno_stub();
+    let data_dir = temp_data_dir();
+    let child = spawn_cli_in(
+        &server,
+        &data_dir,
+        &["station", "Bologna", "--watch", "--interval", "1"],
+    );
+
+    let output = watch_for(child, std::time::Duration::from_millis(2500));
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(
+        stdout
+            .matches("Watch mode: refreshing every second.")
+            .count()
+            >= 2
+    );
+    assert!(stdout.contains("PORRETTA TERME"));
+}
+
+#[cfg(unix)]
+#[test]
+fn station_watch_survives_a_failed_refresh() {
+    let server = StubServer::start(vec![
+        text("autocompletaStazione/Bologna", STATION_AUTOCOMPLETE),
+        json("arrivi/", ARRIVALS),
+        // The second departure board is malformed
+        json_sequence(
+            "partenze/",
+            vec![
+                DEPARTURES.to_string(),
+                "{".to_string(),
+                DEPARTURES.to_string(),
+            ],
+        ),
+    ]);
+    let data_dir = temp_data_dir();
+    let child = spawn_cli_in(
+        &server,
+        &data_dir,
+        &["station", "Bologna", "--watch", "--interval", "1"],
+    );
+
+    let output = watch_for(child, std::time::Duration::from_millis(3500));
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(
+        server
+            .requests()
+            .iter()
+            .filter(|r| r.contains("partenze/"))
+            .count()
+            >= 3
+    );
+    assert!(
+        stdout
+            .matches("Watch mode: refreshing every second.")
+            .count()
+            >= 2
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn station_watch_records_unchanged_trains_once() {
+    let (arrivals, departures) = boards_with_passed_train();
+    let server = viaggiatreno_stub_with_boards(&arrivals, &departures);
+    let data_dir = temp_data_dir();
+
+    let child = spawn_cli_in(
+        &server,
+        &data_dir,
+        &["station", "Bologna", "--watch", "--interval", "1"],
+    );
+    let output = watch_for(child, std::time::Duration::from_millis(2500));
+    let history = std::fs::read_to_string(data_dir.join("history.jsonl")).unwrap();
+
+    assert!(output.status.success());
+    // The arrival and the departure of FR 9612, recorded on the first refresh only
+    assert_eq!(history.lines().count(), 2);
+}
+
+#[test]
+fn history_read_error_is_not_invalid_input() {
+    let server = viaggiatreno_stub();
+    let data_dir = temp_data_dir();
+    std::fs::create_dir_all(data_dir.join("history.jsonl")).unwrap();
+
+    let output = run_cli_in(
+        &server,
+        &data_dir,
+        &["history", "9612", "--station", "Roma"],
+    );
+
+    assert_eq!(output.status.code(), Some(8));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("could not read the delay history"));
+}
+
+#[test]
+fn interval_requires_watch() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "9612", "--interval", "10"]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[cfg(unix)]
+#[test]
+fn track_watch_runs_event_command() {
+    let mut updated_status: serde_json::Value = serde_json::from_str(TRAIN_STATUS).unwrap();
+    updated_status["ritardo"] = 20.into();
+    updated_status["fermate"][2]["binarioEffettivoArrivoDescrizione"] = "12".into();
+    let server = StubServer::start(vec![
+        text("cercaNumeroTrenoTrenoAutocomplete/9612", TRAIN_AUTOCOMPLETE),
+        json_sequence(
+            "andamentoTreno/S09218/9612/1792274400000",
+            vec![TRAIN_STATUS.to_string(), updated_status.to_string()],
+        ),
+    ]);
+    let events_dir = temp_data_dir();
+    std::fs::create_dir_all(&events_dir).unwrap();
+    let events_file = events_dir.join("events.jsonl");
+    let command = format!(
+        "cat >> {} && echo $INFOTRENO_EVENT >> {}",
+        events_file.display(),
+        events_dir.join("kinds").display()
+    );
+
+    let data_dir = temp_data_dir();
+    let child = spawn_cli_in(
+        &server,
+        &data_dir,
+        &[
+            "track",
+            "9612",
+            "--watch",
+            "--interval",
+            "1",
+            "--delay-alert",
+            "15",
+            "--on-event",
+            &command,
+        ],
+    );
+    let output = watch_for(child, std::time::Duration::from_millis(2500));
+    assert!(output.status.success());
+
+    // The commands run in the background, in no particular order
+    let kinds = std::fs::read_to_string(events_dir.join("kinds")).unwrap();
+    let mut kinds: Vec<_> = kinds.lines().collect();
+    kinds.sort_unstable();
+    assert_eq!(
+        kinds,
+        [
+            "delay_changed",
+            "delay_threshold_crossed",
+            "platform_changed"
+        ]
+    );
+    let events: Vec<serde_json::Value> = std::fs::read_to_string(events_file)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    let event = |kind: &str| events.iter().find(|e| e["data"]["event"] == kind).unwrap();
+    assert_eq!(events[0]["kind"], "train_event");
+    assert_eq!(event("delay_changed")["data"]["previous_delay"], 3);
+    assert_eq!(event("delay_changed")["data"]["delay"], 20);
+    assert_eq!(
+        event("platform_changed")["data"]["station"],
+        "FIRENZE S. M. NOVELLA"
+    );
+    assert_eq!(event("platform_changed")["data"]["platform"], "12");
+}
+
+#[cfg(unix)]
+#[test]
+fn track_watch_does_not_wait_for_event_command() {
+    let server = updated_train_status_stub();
+
+    let data_dir = temp_data_dir();
+    let child = spawn_cli_in(
+        &server,
+        &data_dir,
+        &[
+            "track",
+            "9612",
+            "--watch",
+            "--interval",
+            "1",
+            "--on-event",
+            "exec sleep 10 > /dev/null",
+        ],
+    );
+    let output = watch_for(child, std::time::Duration::from_millis(3500));
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    // Refreshed again after the event, while the command is still running
+    assert!(stdout.matches("Train FR 9612").count() >= 3);
+}
+
+fn updated_train_status_stub() -> StubServer {
+    let mut updated_status: serde_json::Value = serde_json::from_str(TRAIN_STATUS).unwrap();
+    updated_status["ritardo"] = 20.into();
+    StubServer::start(vec![
+        text("cercaNumeroTrenoTrenoAutocomplete/9612", TRAIN_AUTOCOMPLETE),
+        json_sequence(
+            "andamentoTreno/S09218/9612/1792274400000",
+            vec![TRAIN_STATUS.to_string(), updated_status.to_string()],
+        ),
+    ])
+}
+
+#[cfg(unix)]
+fn watch_for(child: std::process::Child, duration: std::time::Duration) -> std::process::Output {
+    std::thread::sleep(duration);
+    std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn track_watch_posts_events_to_webhook_with_retries() {
+    let server = updated_train_status_stub();
+    let webhook = WebhookListener::start(vec![503, 200]);
+
+    let data_dir = temp_data_dir();
+    let child = spawn_cli_in(
+        &server,
+        &data_dir,
+        &[
+            "track",
+            "9612",
+            "--watch",
+            "--interval",
+            "1",
+            "--webhook",
+            &webhook.url,
+        ],
+    );
+    let output = watch_for(child, std::time::Duration::from_millis(3000));
+
+    assert!(output.status.success());
+    let bodies = webhook.bodies();
+    // The first request fails and is retried
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0], bodies[1]);
+    let event: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+    assert_eq!(event["kind"], "train_event");
+    assert_eq!(event["data"]["event"], "delay_changed");
+    assert_eq!(event["data"]["train_label"], "FR 9612");
+    assert_eq!(event["data"]["delay"], 20);
+}
+
+#[cfg(unix)]
+#[test]
+fn track_watch_does_not_wait_for_webhook() {
+    let server = updated_train_status_stub();
+    // Accepts connections without ever answering
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+    let data_dir = temp_data_dir();
+    let child = spawn_cli_in(
+        &server,
+        &data_dir,
+        &[
+            "track",
+            "9612",
+            "--watch",
+            "--interval",
+            "1",
+            "--webhook",
+            &url,
+        ],
+    );
+    let output = watch_for(child, std::time::Duration::from_millis(3500));
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    // Refreshed again after the event, while the request is still pending
+    assert!(stdout.matches("Train FR 9612").count() >= 3);
+}
+
+#[test]
+fn invalid_webhook_url_is_invalid_input() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &["track", "9612", "--watch", "--webhook", "not a url"],
+    );
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn track_prints_summary_of_several_trains() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "9612", "8805"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(lines.iter().any(|l| l.contains("FR 9612")
+        && l.contains("NAPOLI CENTRALE - MILANO CENTRALE")
+        && l.contains("ROMA TERMINI")
+        && l.contains("+3")
+        && l.contains("FIRENZE S. M. NOVELLA")));
+    assert!(lines.iter().any(|l| l.contains("IC 8805")
+        && l.contains("MILANO CENTRALE - LECCE")
+        && l.contains("MODENA")
+        && l.contains("+12")
+        && l.contains("BOLOGNA CENTRALE")));
+    assert!(!stdout.contains("Stops:"));
+}
+
+#[test]
+fn track_index_is_rejected_with_several_trains() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "2143", "9612", "--index", "2"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--index"));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn track_shows_unavailable_train_among_several() {
+    let server = StubServer::start(vec![
+        text("cercaNumeroTrenoTrenoAutocomplete/9612", TRAIN_AUTOCOMPLETE),
+        text(
+            "cercaNumeroTrenoTrenoAutocomplete/8805",
+            INTERCITY_AUTOCOMPLETE,
+        ),
+        json("andamentoTreno/S09218/9612/1792274400000", TRAIN_STATUS),
+    ]);
+    let output = run_cli(&server, &["track", "9612", "8805"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    let lines: Vec<_> = stdout.lines().collect();
+    assert!(
+        lines
+            .iter()
+            .any(|l| l.contains("FR 9612") && l.contains("FIRENZE S. M. NOVELLA"))
+    );
+    assert!(
+        lines
+            .iter()
+            .any(|l| l.starts_with("8805") && l.contains("Unavailable"))
+    );
+}
+
+#[test]
+fn track_expands_stops_of_selected_trains() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "9612", "8805", "--stops-of", "8805"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert_eq!(stdout.matches("Stops:").count(), 1);
+    assert!(stdout.contains("LECCE - platform"));
+}
+
+#[test]
+fn track_several_trains_ndjson_output() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["--output", "ndjson", "track", "9612", "8805"]);
+    let lines = json_lines(&output);
+
+    assert!(output.status.success());
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["kind"], "train_status");
+    assert_eq!(lines[1]["data"]["number"], 8805);
+}
+
+#[test]
+fn connection_makes_change_with_enough_transfer_time() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["connection", "9612", "8805", "--at", "Bologna"]);
+    let stdout = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Connection at BOLOGNA CENTRALE"));
+    assert!(stdout.contains("Transfer time: 25 min (minimum 5 min)"));
+    assert!(stdout.contains("MAKE"));
+}
+
+#[test]
+fn connection_misses_change_below_minimum_transfer() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &[
+            "--output",
+            "json",
+            "connection",
+            "9612",
+            "8805",
+            "--min-transfer",
+            "30",
+        ],
+    );
+    let document: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(document["kind"], "connection");
+    assert_eq!(document["data"]["station_code"], "S05043");
+    assert_eq!(
+        document["data"]["estimated_arrival"],
+        "2026-10-18T10:20:00+02:00"
+    );
+    assert_eq!(
+        document["data"]["estimated_departure"],
+        "2026-10-18T10:45:00+02:00"
+    );
+    assert_eq!(document["data"]["transfer"], 25);
+    assert_eq!(document["data"]["verdict"], "miss");
+}
+
+#[test]
+fn connection_at_station_not_served_is_not_found() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["connection", "9612", "8805", "--at", "Roma"]);
+
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn connection_skips_the_terminus_of_the_departing_train() {
+    // The departing train ends at Roma, where the arriving train stops first
+    let mut departing: serde_json::Value = serde_json::from_str(INTERCITY_STATUS).unwrap();
+    let terminus = departing["fermate"]
+        .as_array_mut()
+        .unwrap()
+        .last_mut()
+        .unwrap();
+    terminus["id"] = serde_json::json!("S08409");
+    terminus["stazione"] = serde_json::json!("ROMA TERMINI");
+    let server = StubServer::start(vec![
+        text("cercaNumeroTrenoTrenoAutocomplete/9612", TRAIN_AUTOCOMPLETE),
+        text(
+            "cercaNumeroTrenoTrenoAutocomplete/8805",
+            INTERCITY_AUTOCOMPLETE,
+        ),
+        json("andamentoTreno/S09218/9612/", TRAIN_STATUS),
+        json("andamentoTreno/S01700/8805/", &departing.to_string()),
+    ]);
+    let output = run_cli(&server, &["--output", "json", "connection", "9612", "8805"]);
+    let document: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(document["data"]["station_code"], "S05043");
+    assert_eq!(document["data"]["verdict"], "make");
+}
+
+#[test]
+fn connection_as_csv_is_one_row() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(
+        &server,
+        &[
+            "--output",
+            "csv",
+            "connection",
+            "9612",
+            "8805",
+            "--at",
+            "Bologna",
+        ],
+    );
+    let stdout = stdout(&output);
+    let lines: Vec<_> = stdout.lines().collect();
+
+    assert!(output.status.success());
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("station,station_code,arriving_train,"));
+    assert!(lines[1].starts_with("BOLOGNA CENTRALE,S05043,FR 9612,"));
+    assert!(lines[1].ends_with(",25,5,make"));
+}
+
+/// Two trains sharing the code 8805 today, from different origins.
+fn reused_number_stub() -> StubServer {
+    StubServer::start(vec![
+        text(
+            "cercaNumeroTrenoTrenoAutocomplete/8805",
+            "8805 - MILANO CENTRALE - 18/10/26|8805-S01700-1792274400000\n\
+             8805 - TORINO PORTA NUOVA - 18/10/26|8805-S00219-1792274400000\n",
+        ),
+        json("andamentoTreno/S01700/8805/", INTERCITY_STATUS),
+        json("andamentoTreno/S00219/8805/", INTERCITY_STATUS),
+    ])
+}
+
+#[test]
+fn connection_ambiguous_train_suggests_category() {
+    let server = reused_number_stub();
+    let output = run_cli(
+        &server,
+        &["connection", "8805", "8805", "--non-interactive"],
+    );
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Give the category of the train"));
+}
+
+#[test]
+fn track_date_requests_the_run_of_that_day() {
+    let server = viaggiatreno_stub();
+    let server_yesterday = StubServer::start(vec![
+        text(
+            "cercaNumeroTrenoTrenoAutocomplete/8805",
+            INTERCITY_AUTOCOMPLETE,
+        ),
+        json("andamentoTreno/S01700/8805/1792188000000", INTERCITY_STATUS),
+    ]);
+
+    // The run listed by the autocomplete
+    let output = run_cli(&server, &["track", "8805", "--date", "2026-10-18"]);
+    assert!(output.status.success());
+
+    // The previous day, at midnight in Italy
+    let output = run_cli(
+        &server_yesterday,
+        &["track", "8805", "--date", "2026-10-17", "--no-history"],
+    );
+    assert!(output.status.success());
+    assert!(
+        server_yesterday
+            .requests()
+            .iter()
+            .any(|r| r.contains("andamentoTreno/S01700/8805/1792188000000"))
+    );
+}
+
+#[test]
+fn track_origin_picks_the_matching_train() {
+    let server = reused_number_stub();
+
+    let output = run_cli(&server, &["track", "8805", "--non-interactive"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Use --index, --origin or --date"));
+
+    let output = run_cli(
+        &server,
+        &["track", "8805", "--origin", "torino", "--no-history"],
+    );
+    assert!(output.status.success());
+    assert!(
+        server
+            .requests()
+            .iter()
+            .any(|r| r.contains("andamentoTreno/S00219/8805/1792274400000"))
+    );
+
+    let output = run_cli(
+        &server,
+        &[
+            "track",
+            "8805",
+            "--origin",
+            "S01700",
+            "--date",
+            "2026-10-17",
+            "--no-history",
+        ],
+    );
+    assert!(output.status.success());
+    assert!(
+        server
+            .requests()
+            .iter()
+            .any(|r| r.contains("andamentoTreno/S01700/8805/1792188000000"))
+    );
+}
+
+#[test]
+fn track_invalid_date_is_rejected() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "8805", "--date", "17/10/2026"]);
+
+    assert!(!output.status.success());
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn track_accepts_train_labels() {
+    let server = viaggiatreno_stub();
+
+    for label in ["FR 9612", "fr9612"] {
+        let output = run_cli(&server, &["track", label, "--no-history"]);
+        assert!(output.status.success());
+        assert!(stdout(&output).contains("Train FR 9612, NAPOLI CENTRALE - MILANO CENTRALE"));
+    }
+
+    let output = run_cli(&server, &["track", "FR 96x12"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("\"FR 96x12\" is not valid"));
+}
+
+#[test]
+fn track_label_category_picks_the_matching_train() {
+    let regional_status = INTERCITY_STATUS
+        .replace("\"categoria\": \"IC\"", "\"categoria\": \"REG\"")
+        .replace("IC 8805", "REG 8805");
+    let server = StubServer::start(vec![
+        text(
+            "cercaNumeroTrenoTrenoAutocomplete/8805",
+            "8805 - MILANO CENTRALE - 18/10/26|8805-S01700-1792274400000\n\
+             8805 - TORINO PORTA NUOVA - 18/10/26|8805-S00219-1792274400000\n",
+        ),
+        json("andamentoTreno/S01700/8805/", INTERCITY_STATUS),
+        json("andamentoTreno/S00219/8805/", &regional_status),
+    ]);
+
+    let output = run_cli(
+        &server,
+        &["track", "REG 8805", "--non-interactive", "--no-history"],
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Train REG 8805"));
+
+    let output = run_cli(
+        &server,
+        &["track", "IC 8805", "--non-interactive", "--no-history"],
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Train IC 8805"));
+
+    let output = run_cli(&server, &["track", "FR 8805", "--non-interactive"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("No train found with the code FR 8805")
+    );
+}
+
+#[test]
+fn track_unquoted_label_is_one_code() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "FR", "9612", "8805", "--no-history"]);
+    let summary = stdout(&output);
+
+    assert!(output.status.success());
+    assert!(summary.contains("FR 9612"));
+    assert!(summary.contains("IC 8805"));
+
+    let output = run_cli(&server, &["track", "FR", "9612"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Train FR 9612"));
+
+    let output = run_cli(&server, &["track", "FR"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn track_shows_provisions_and_altered_stops() {
+    let server = viaggiatreno_stub();
+    let output = run_cli(&server, &["track", "2143", "--stops", "--no-history"]);
+    let stdout = stdout(&output);
+    let lines: Vec<_> = stdout.lines().collect();
+
+    assert!(output.status.success());
+    assert_eq!(
+        lines[0],
+        "Train REG 2143, BOLOGNA CENTRALE - PORRETTA TERME "
+    );
+    assert_eq!(lines[1], "Treno limitato a MARZABOTTO");
+    assert_eq!(lines[2], "Fermata straordinaria a CASALECCHIO DI RENO");
+    assert!(stdout.contains("CASALECCHIO DI RENO - platform -- (added stop)"));
+    assert!(stdout.contains("SASSO MARCONI - canceled"));
+    assert!(stdout.contains("PORRETTA TERME - canceled"));
+    assert!(stdout.contains("Next stop: MARZABOTTO"));
+
+    let output = run_cli(&server, &["track", "2143", "-o", "json", "--no-history"]);
+    let train = &json_lines(&output)[0]["data"];
+
+    assert_eq!(train["run_type"], "limited");
+    assert_eq!(train["provisions"][0], "Treno limitato a MARZABOTTO");
+    assert_eq!(train["stops"][2]["kind"], "canceled");
+    assert_eq!(train["stops"][1]["kind"], "extraordinary");
+}
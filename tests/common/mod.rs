@@ -0,0 +1,244 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A recorded response, served for every request whose path contains `path`.
+pub struct Route {
+    pub path: &'static str,
+    pub content_type: &'static str,
+    /// Bodies served in turn to successive requests, the last one being repeated
+    pub bodies: Vec<String>,
+    served: AtomicUsize,
+}
+
+impl Route {
+    fn next_body(&self) -> &str {
+        let index = self.served.fetch_add(1, Ordering::Relaxed);
+        &self.bodies[index.min(self.bodies.len() - 1)]
+    }
+}
+
+pub fn json(path: &'static str, body: &str) -> Route {
+    json_sequence(path, vec![body.to_string()])
+}
+
+/// A JSON route serving a different body to each request, such as the updated statuses of a train.
+pub fn json_sequence(path: &'static str, bodies: Vec<String>) -> Route {
+    Route {
+        path,
+        content_type: "application/json",
+        bodies,
+        served: AtomicUsize::new(0),
+    }
+}
+
+pub fn text(path: &'static str, body: &str) -> Route {
+    Route {
+        path,
+        content_type: "text/plain",
+        bodies: vec![body.to_string()],
+        served: AtomicUsize::new(0),
+    }
+}
+
+/// Local HTTP stub serving recorded Viaggiatreno responses.
+pub struct StubServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl StubServer {
+    pub fn start(routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let served_requests = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                handle_connection(stream, &routes, &served_requests);
+            }
+        });
+
+        Self { base_url, requests }
+    }
+
+    /// Paths of the requests received so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, routes: &[Route], requests: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Skip the headers
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if header.trim().is_empty() => break,
+            Ok(_) => {}
+        }
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+    requests.lock().unwrap().push(path.clone());
+
+    let response = match routes.iter().find(|r| path.contains(r.path)) {
+        Some(route) => {
+            let body = route.next_body();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                route.content_type,
+                body.len(),
+                body
+            )
+        }
+        None => {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        }
+    };
+
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Local HTTP listener recording the bodies of the requests it receives, like a webhook receiver.
+pub struct WebhookListener {
+    pub url: String,
+    bodies: Arc<Mutex<Vec<String>>>,
+}
+
+impl WebhookListener {
+    /// Starts a listener answering with the given statuses in turn, the last one being repeated.
+    pub fn start(statuses: Vec<u16>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+
+        let received_bodies = Arc::clone(&bodies);
+        thread::spawn(move || {
+            for (index, stream) in listener.incoming().flatten().enumerate() {
+                let status = statuses[index.min(statuses.len() - 1)];
+                receive_request(stream, status, &received_bodies);
+            }
+        });
+
+        Self { url, bodies }
+    }
+
+    /// Bodies of the requests received so far.
+    pub fn bodies(&self) -> Vec<String> {
+        self.bodies.lock().unwrap().clone()
+    }
+}
+
+fn receive_request(mut stream: TcpStream, status: u16, bodies: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if header.trim().is_empty() => break,
+            Ok(_) => {
+                if let Some((name, value)) = header.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+    bodies
+        .lock()
+        .unwrap()
+        .push(String::from_utf8_lossy(&body).to_string());
+
+    let response =
+        format!("HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// An empty data directory, where the CLI stores the delay history, removed when dropped.
+pub struct TempDataDir(PathBuf);
+
+impl Deref for TempDataDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDataDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDataDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub fn temp_data_dir() -> TempDataDir {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "infotreno-test-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    TempDataDir(dir)
+}
+
+/// Runs the CLI against the given server, with colors disabled and an empty data directory.
+pub fn run_cli(server: &StubServer, args: &[&str]) -> Output {
+    run_cli_in(server, &temp_data_dir(), args)
+}
+
+/// Runs the CLI against the given server, with colors disabled and the given data directory.
+pub fn run_cli_in(server: &StubServer, data_dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_infotreno-cli"))
+        .args(args)
+        .env("INFOTRENO_BASE_URL", &server.base_url)
+        .env("INFOTRENO_DATA_DIR", data_dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+/// Starts the CLI in the background against the given server, with colors disabled, the given data
+/// directory and the standard output captured.
+pub fn spawn_cli_in(server: &StubServer, data_dir: &Path, args: &[&str]) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_infotreno-cli"))
+        .args(args)
+        .env("INFOTRENO_BASE_URL", &server.base_url)
+        .env("INFOTRENO_DATA_DIR", data_dir)
+        .env("NO_COLOR", "1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
@@ -0,0 +1,104 @@
+use viaggiatreno::TrainStatus;
+use viaggiatreno::estimate::RecoveryModel;
+
+/// IC 8805 has left Milano 12 minutes late; Bologna is scheduled 10:27-10:33, after 132 minutes
+/// of running, and Lecce at 19:00.
+const TRAIN_STATUS: &str = include_str!("fixtures/andamento_treno_8805.json");
+
+fn train_status() -> TrainStatus {
+    serde_json::from_str(TRAIN_STATUS).unwrap()
+}
+
+fn delays(model: &RecoveryModel, status: &TrainStatus) -> Vec<Option<(i64, i64)>> {
+    model
+        .estimate(status)
+        .iter()
+        .map(|e| e.map(|e| (e.arrival_delay, e.departure_delay)))
+        .collect()
+}
+
+#[test]
+fn reached_stops_have_no_estimate() {
+    let status = train_status();
+
+    assert!(RecoveryModel::default().estimate(&status)[0].is_none());
+}
+
+#[test]
+fn without_slack_the_delay_is_propagated() {
+    let status = train_status();
+    let model = RecoveryModel {
+        recovery_rate: 0.0,
+        min_dwell: 60,
+        ..Default::default()
+    };
+
+    let estimates = model.estimate(&status);
+    let bologna = estimates[1].unwrap();
+
+    assert_eq!(
+        delays(&model, &status),
+        [None, Some((12, 12)), Some((12, 12))]
+    );
+    assert_eq!(
+        bologna.arrival.unwrap().to_rfc3339(),
+        "2026-10-18T10:39:00+02:00"
+    );
+    assert_eq!(
+        bologna.departure.unwrap().to_rfc3339(),
+        "2026-10-18T10:45:00+02:00"
+    );
+}
+
+#[test]
+fn dwell_time_absorbs_delay() {
+    let status = train_status();
+    let model = RecoveryModel {
+        recovery_rate: 0.0,
+        ..Default::default()
+    };
+
+    // 6 minutes scheduled at Bologna, 1 of which is needed to board
+    assert_eq!(delays(&model, &status), [None, Some((12, 7)), Some((7, 7))]);
+}
+
+#[test]
+fn recovery_margins_absorb_delay() {
+    let status = train_status();
+    let model = RecoveryModel::default();
+
+    // 2% of 132 minutes is recovered before Bologna, the dwell time absorbs 5 more minutes and
+    // the long run to Lecce the rest
+    assert_eq!(delays(&model, &status), [None, Some((9, 4)), Some((0, 0))]);
+    assert_eq!(
+        model.estimate(&status)[1]
+            .unwrap()
+            .arrival
+            .unwrap()
+            .to_rfc3339(),
+        "2026-10-18T10:36:00+02:00"
+    );
+}
+
+#[test]
+fn learned_segment_recovery_replaces_margin() {
+    let status = train_status();
+    let mut model = RecoveryModel::default();
+    model
+        .segment_recovery
+        .insert(("S01700".to_string(), "S05043".to_string()), -3.0);
+
+    // The train usually loses 3 minutes before Bologna
+    assert_eq!(delays(&model, &status)[1], Some((15, 10)));
+}
+
+#[test]
+fn early_train_waits_for_scheduled_departure() {
+    let mut status = train_status();
+    status.delay = Some(-4);
+
+    assert_eq!(
+        delays(&RecoveryModel::default(), &status),
+        [None, Some((-4, 0)), Some((0, 0))]
+    );
+}
@@ -0,0 +1,146 @@
+use viaggiatreno::events::{DiffOptions, TrainEvent, diff};
+use viaggiatreno::{StopKind, TrainStatus};
+
+const TRAIN_STATUS: &str = include_str!("fixtures/andamento_treno_9612.json");
+
+fn train_status() -> TrainStatus {
+    serde_json::from_str(TRAIN_STATUS).unwrap()
+}
+
+#[test]
+fn same_status_has_no_events() {
+    let status = train_status();
+
+    assert!(diff(&status, &status, &DiffOptions::default()).is_empty());
+}
+
+#[test]
+fn detects_platform_change_from_scheduled_platform() {
+    let previous = train_status();
+    let mut current = previous.clone();
+    current.stops[2].actual_arrival_platform = Some("12".to_string());
+
+    assert_eq!(
+        diff(&previous, &current, &DiffOptions::default()),
+        [TrainEvent::PlatformChanged {
+            station: "FIRENZE S. M. NOVELLA".to_string(),
+            from: previous.stops[2].scheduled_platform().unwrap().to_string(),
+            to: "12".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn detects_delay_changes_above_threshold() {
+    let previous = train_status();
+    let mut current = previous.clone();
+
+    current.delay = Some(7);
+    assert!(diff(&previous, &current, &DiffOptions::default()).is_empty());
+
+    current.delay = Some(18);
+    assert_eq!(
+        diff(&previous, &current, &DiffOptions::default()),
+        [TrainEvent::DelayChanged { from: 3, to: 18 }]
+    );
+}
+
+#[test]
+fn detects_cancellation() {
+    let previous = train_status();
+    let mut current = previous.clone();
+    current.provision = Some(1);
+
+    assert_eq!(
+        diff(&previous, &current, &DiffOptions::default()),
+        [TrainEvent::Canceled]
+    );
+}
+
+#[test]
+fn detects_skipped_stops() {
+    let previous = train_status();
+    let mut current = previous.clone();
+    current.stops[2].kind = StopKind::Canceled;
+    current.stops.remove(3);
+
+    assert_eq!(
+        diff(&previous, &current, &DiffOptions::default()),
+        [
+            TrainEvent::StopSkipped {
+                station: "FIRENZE S. M. NOVELLA".to_string()
+            },
+            TrainEvent::StopSkipped {
+                station: "BOLOGNA CENTRALE".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn detects_departure_and_arrivals() {
+    let mut previous = train_status();
+    previous.not_departed = Some(true);
+    for stop in &mut previous.stops[1..] {
+        stop.kind = StopKind::Pending;
+    }
+    let mut current = train_status();
+    for stop in &mut current.stops {
+        stop.kind = StopKind::Regular;
+    }
+
+    let events = diff(&previous, &current, &DiffOptions::default());
+
+    assert_eq!(events[0], TrainEvent::Departed);
+    assert!(events.contains(&TrainEvent::ArrivedAtStop {
+        station: "ROMA TERMINI".to_string()
+    }));
+    assert_eq!(
+        events.last(),
+        Some(&TrainEvent::ArrivedAtDestination {
+            station: "MILANO CENTRALE".to_string()
+        })
+    );
+}
+
+#[test]
+fn detects_delay_threshold_crossings() {
+    let previous = train_status();
+    let mut current = previous.clone();
+    current.delay = Some(16);
+    let options = DiffOptions {
+        delay_change: 60,
+        delay_thresholds: vec![5, 15, 30],
+    };
+
+    assert_eq!(
+        diff(&previous, &current, &options),
+        [
+            TrainEvent::DelayThresholdCrossed {
+                threshold: 5,
+                delay: 16
+            },
+            TrainEvent::DelayThresholdCrossed {
+                threshold: 15,
+                delay: 16
+            },
+        ]
+    );
+}
+
+#[test]
+fn detects_platform_assignment() {
+    let mut previous = train_status();
+    previous.stops[4].scheduled_arrival_platform = None;
+    previous.stops[4].actual_arrival_platform = None;
+    let mut current = previous.clone();
+    current.stops[4].actual_arrival_platform = Some("7".to_string());
+
+    assert_eq!(
+        diff(&previous, &current, &DiffOptions::default()),
+        [TrainEvent::PlatformAssigned {
+            station: "MILANO CENTRALE".to_string(),
+            platform: "7".to_string(),
+        }]
+    );
+}
@@ -0,0 +1,141 @@
+use chrono::TimeZone;
+use chrono_tz::Europe::Rome;
+use viaggiatreno::{RunType, StationBoardEntry, StopKind, TrainStatus};
+
+const TRAIN_STATUS: &str = include_str!("fixtures/andamento_treno_9612.json");
+/// REG 2143, limited to Marzabotto, with an added stop and a canceled one.
+const LIMITED_TRAIN_STATUS: &str = include_str!("fixtures/andamento_treno_2143.json");
+const ARRIVALS: &str = include_str!("fixtures/arrivi_S05043.json");
+const DEPARTURES: &str = include_str!("fixtures/partenze_S05043.json");
+
+#[test]
+fn deserializes_train_status() {
+    let status: TrainStatus = serde_json::from_str(TRAIN_STATUS).unwrap();
+
+    assert_eq!(status.number, Some(9612));
+    assert_eq!(status.label.as_deref(), Some("FR 9612"));
+    assert_eq!(status.origin.as_deref(), Some("NAPOLI CENTRALE"));
+    assert_eq!(status.destination.as_deref(), Some("MILANO CENTRALE"));
+    assert_eq!(status.delay, Some(3));
+    assert_eq!(
+        status.last_detection_time,
+        Some(Rome.with_ymd_and_hms(2026, 10, 18, 8, 3, 0).unwrap())
+    );
+    assert!(!status.is_canceled());
+    assert!(!status.is_not_departed());
+    assert!(!status.is_arrived());
+    assert_eq!(status.stops.len(), 5);
+}
+
+#[test]
+fn deserializes_stops() {
+    let status: TrainStatus = serde_json::from_str(TRAIN_STATUS).unwrap();
+    let stops = &status.stops;
+
+    assert_eq!(stops[0].station_code, "S09218");
+    assert_eq!(stops[0].scheduled_arrival, None);
+    assert_eq!(
+        stops[0].scheduled_departure,
+        Some(Rome.with_ymd_and_hms(2026, 10, 18, 6, 40, 0).unwrap())
+    );
+    assert_eq!(stops[1].kind, StopKind::Regular);
+    assert_eq!(stops[1].scheduled_platform(), Some("8"));
+    assert_eq!(stops[1].actual_platform(), Some("10"));
+    assert_eq!(stops[2].kind, StopKind::Pending);
+    assert_eq!(stops[2].actual_platform(), None);
+    assert_eq!(stops[4].scheduled_departure, None);
+
+    let next_stop = status.next_stop().unwrap();
+    assert_eq!(next_stop.station, "FIRENZE S. M. NOVELLA");
+}
+
+#[test]
+fn deserializes_stop_kinds() {
+    let kinds: Vec<StopKind> = serde_json::from_str("[0, 1, 2, 3, 7]").unwrap();
+
+    assert_eq!(
+        kinds,
+        [
+            StopKind::Pending,
+            StopKind::Regular,
+            StopKind::Extraordinary,
+            StopKind::Canceled,
+            StopKind::Unknown(7)
+        ]
+    );
+}
+
+#[test]
+fn missing_fields_do_not_fail() {
+    let status: TrainStatus =
+        serde_json::from_str(r#"{"fermate": [{"stazione": "ROMA TERMINI"}], "ritardo": null}"#)
+            .unwrap();
+
+    assert_eq!(status.label, None);
+    assert_eq!(status.delay, None);
+    assert_eq!(status.stops[0].kind, StopKind::Pending);
+    assert_eq!(status.stops[0].scheduled_arrival, None);
+}
+
+#[test]
+fn deserializes_station_boards() {
+    let arrivals: Vec<StationBoardEntry> = serde_json::from_str(ARRIVALS).unwrap();
+    let departures: Vec<StationBoardEntry> = serde_json::from_str(DEPARTURES).unwrap();
+
+    assert_eq!(arrivals.len(), 3);
+    assert_eq!(arrivals[0].label.trim(), "FR 9612");
+    assert_eq!(arrivals[0].origin.as_deref(), Some("NAPOLI CENTRALE"));
+    assert_eq!(arrivals[0].arrival_time.as_deref(), Some("10:17"));
+    assert_eq!(
+        arrivals[0].scheduled_arrival,
+        Some(Rome.with_ymd_and_hms(2026, 10, 18, 10, 17, 0).unwrap())
+    );
+    assert_eq!(arrivals[2].delay, None);
+    assert_eq!(arrivals[2].scheduled_arrival_platform, None);
+
+    assert_eq!(departures[2].destination.as_deref(), Some("LECCE"));
+    assert_eq!(departures[2].delay, Some(12));
+    assert_eq!(
+        departures[1].actual_departure_platform.as_deref(),
+        Some("3")
+    );
+    assert!(departures[1].has_category("reg"));
+    assert!(!departures[1].has_category("FR"));
+}
+
+#[test]
+fn deserializes_limited_run() {
+    let status: TrainStatus = serde_json::from_str(LIMITED_TRAIN_STATUS).unwrap();
+
+    assert_eq!(status.run_type, Some(RunType::Limited));
+    assert!(status.is_altered());
+    assert!(!status.is_canceled());
+    assert_eq!(
+        status.provisions(),
+        [
+            "Treno limitato a MARZABOTTO",
+            "Fermata straordinaria a CASALECCHIO DI RENO"
+        ]
+    );
+    assert_eq!(status.stops[1].kind, StopKind::Extraordinary);
+    assert_eq!(status.stops[2].kind, StopKind::Canceled);
+    assert_eq!(status.next_stop().unwrap().station, "MARZABOTTO");
+}
+
+#[test]
+fn arrived_skips_canceled_final_stops() {
+    let mut status: TrainStatus = serde_json::from_str(LIMITED_TRAIN_STATUS).unwrap();
+    assert!(!status.is_arrived());
+
+    status.stops[3].kind = StopKind::Regular;
+    assert!(status.is_arrived());
+}
+
+#[test]
+fn total_cancellation_from_run_type() {
+    let mut status: TrainStatus = serde_json::from_str(LIMITED_TRAIN_STATUS).unwrap();
+    status.run_type = Some(RunType::from("ST".to_string()));
+
+    assert!(status.is_canceled());
+    assert!(!status.is_altered());
+}
@@ -0,0 +1,129 @@
+use chrono::{NaiveDate, TimeDelta, TimeZone};
+use chrono_tz::Europe::Rome;
+use viaggiatreno::TrainStatus;
+use viaggiatreno::time::{format_time, from_milliseconds};
+
+/// Intercity Notte leaving Milano on the evening of 24/10/2026 and arriving in Reggio Calabria
+/// the next morning, on the night the clocks go back.
+const OVERNIGHT_TRAIN_STATUS: &str = include_str!("fixtures/andamento_treno_1963.json");
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn uses_the_offset_in_effect_at_each_instant() {
+    // 15/01/2026 12:00 UTC and 15/07/2026 12:00 UTC
+    let winter = from_milliseconds(1768478400000).unwrap();
+    let summer = from_milliseconds(1784116800000).unwrap();
+
+    assert_eq!(winter.to_rfc3339(), "2026-01-15T13:00:00+01:00");
+    assert_eq!(summer.to_rfc3339(), "2026-07-15T14:00:00+02:00");
+}
+
+#[test]
+fn skips_the_hour_when_clocks_go_forward() {
+    // 29/03/2026 00:59 and 01:00 UTC
+    let before = from_milliseconds(1774745940000).unwrap();
+    let after = from_milliseconds(1774746000000).unwrap();
+
+    assert_eq!(before.to_rfc3339(), "2026-03-29T01:59:00+01:00");
+    assert_eq!(after.to_rfc3339(), "2026-03-29T03:00:00+02:00");
+    assert_eq!(format_time(before, date(2026, 3, 29)), "01:59");
+    assert_eq!(format_time(after, date(2026, 3, 29)), "03:00");
+}
+
+#[test]
+fn repeats_the_hour_when_clocks_go_back() {
+    // 25/10/2026 00:30 and 01:30 UTC
+    let first = from_milliseconds(1792888200000).unwrap();
+    let second = from_milliseconds(1792891800000).unwrap();
+
+    assert_eq!(first.to_rfc3339(), "2026-10-25T02:30:00+02:00");
+    assert_eq!(second.to_rfc3339(), "2026-10-25T02:30:00+01:00");
+    assert_eq!(second - first, TimeDelta::hours(1));
+}
+
+#[test]
+fn formats_the_date_when_not_today() {
+    let time = Rome.with_ymd_and_hms(2026, 10, 25, 6, 40, 0).unwrap();
+
+    assert_eq!(format_time(time, date(2026, 10, 25)), "06:40");
+    assert_eq!(format_time(time, date(2026, 10, 24)), "25/10 06:40");
+    assert_eq!(format_time(time, date(2027, 10, 25)), "25/10 06:40");
+}
+
+#[test]
+fn overnight_train_keeps_the_date_of_each_stop() {
+    let status: TrainStatus = serde_json::from_str(OVERNIGHT_TRAIN_STATUS).unwrap();
+    let stops = &status.stops;
+    let departure_day = date(2026, 10, 24);
+
+    assert_eq!(status.departure_date.unwrap().date_naive(), departure_day);
+    assert_eq!(
+        stops[0].scheduled_departure.unwrap().to_rfc3339(),
+        "2026-10-24T21:10:00+02:00"
+    );
+    assert_eq!(
+        stops[4].scheduled_arrival.unwrap().to_rfc3339(),
+        "2026-10-25T12:05:00+01:00"
+    );
+
+    assert_eq!(
+        format_time(stops[0].scheduled_departure.unwrap(), departure_day),
+        "21:10"
+    );
+    assert_eq!(
+        format_time(stops[3].scheduled_arrival.unwrap(), departure_day),
+        "25/10 04:10"
+    );
+    assert_eq!(
+        format_time(stops[0].scheduled_departure.unwrap(), date(2026, 10, 25)),
+        "24/10 21:10"
+    );
+}
+
+#[test]
+fn overnight_train_times_stay_ordered_across_the_repeated_hour() {
+    let status: TrainStatus = serde_json::from_str(OVERNIGHT_TRAIN_STATUS).unwrap();
+    let florence = &status.stops[2];
+
+    // Scheduled in the first 02:00-03:00 hour, reached 15 minutes late in the second one
+    assert_eq!(
+        florence.scheduled_arrival.unwrap().to_rfc3339(),
+        "2026-10-25T02:45:00+02:00"
+    );
+    assert_eq!(
+        florence.actual_arrival.unwrap().to_rfc3339(),
+        "2026-10-25T02:00:00+01:00"
+    );
+    assert_eq!(
+        florence.actual_arrival.unwrap() - florence.scheduled_arrival.unwrap(),
+        TimeDelta::minutes(15)
+    );
+
+    // Bologna 23:28 CEST to Roma 04:10 CET is 5 hours and 42 minutes
+    let bologna_departure = status.stops[1].scheduled_departure.unwrap();
+    let rome_arrival = status.stops[3].scheduled_arrival.unwrap();
+    assert_eq!(
+        rome_arrival - bologna_departure,
+        TimeDelta::minutes(5 * 60 + 42)
+    );
+
+    let times: Vec<_> = status
+        .stops
+        .iter()
+        .filter_map(|stop| stop.scheduled_arrival.or(stop.scheduled_departure))
+        .collect();
+    assert!(times.is_sorted());
+}
+
+#[test]
+fn estimated_time_crosses_midnight() {
+    let status: TrainStatus = serde_json::from_str(OVERNIGHT_TRAIN_STATUS).unwrap();
+    // Bologna is scheduled at 23:20, a 45 minute delay moves the arrival to the next day
+    let estimated = status.stops[1].scheduled_arrival.unwrap() + TimeDelta::minutes(45);
+
+    assert_eq!(format_time(estimated, date(2026, 10, 24)), "25/10 00:05");
+    assert_eq!(format_time(estimated, date(2026, 10, 25)), "00:05");
+}